@@ -0,0 +1,167 @@
+//! Stand-in for a remote camera, serves a moving test pattern to the network frame
+//! sources.
+//!
+//! ```text
+//! frame_server tcp  <listen address>   # e.g. 0.0.0.0:7878
+//! frame_server udp  <target address>   # e.g. 127.0.0.1:7878
+//! frame_server http <listen address>   # stream at http://<address>/
+//! ```
+//!
+//! Frames are 1280x480 side-by-side stereo, YUYV over TCP and UDP, MJPEG over HTTP.
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, UdpSocket},
+    time::{Duration, Instant},
+};
+
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 480;
+const FPS: u32 = 30;
+/// Data sent in each UDP datagram, leaving room for the header.
+const CHUNK_SIZE: usize = 60000;
+const BOUNDARY: &str = "frame";
+
+fn monotonic_now() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Checkerboard scrolling to the right, in RGB.
+fn pattern(frame: u32) -> image::RgbImage {
+    image::RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        let x = (x % (WIDTH / 2) + frame * 4) % (WIDTH / 2);
+        if (x / 40 + y / 40) % 2 == 0 {
+            image::Rgb([230, 230, 230])
+        } else {
+            image::Rgb([30, 60, 120])
+        }
+    })
+}
+
+fn to_yuyv(image: &image::RgbImage) -> Vec<u8> {
+    let yuv = |[r, g, b]: [u8; 3]| {
+        let (r, g, b) = (r as f32, g as f32, b as f32);
+        let y = 0.299 * r + 0.587 * g + 0.114 * b;
+        let u = (b - y) * 0.564 + 128.0;
+        let v = (r - y) * 0.713 + 128.0;
+        (y as u8, u as u8, v as u8)
+    };
+    image
+        .pixels()
+        .collect::<Vec<_>>()
+        .chunks(2)
+        .flat_map(|pair| {
+            let (y0, u, v) = yuv(pair[0].0);
+            let (y1, ..) = yuv(pair[1].0);
+            [y0, u, y1, v]
+        })
+        .collect()
+}
+
+fn header(sequence: u32, timestamp: Duration, length: usize, offset: usize) -> Vec<u8> {
+    let mut header = Vec::with_capacity(36);
+    header.extend_from_slice(b"ICPF");
+    header.extend_from_slice(b"YUYV");
+    header.extend_from_slice(&WIDTH.to_le_bytes());
+    header.extend_from_slice(&HEIGHT.to_le_bytes());
+    header.extend_from_slice(&sequence.to_le_bytes());
+    header.extend_from_slice(&(timestamp.as_micros() as u64).to_le_bytes());
+    header.extend_from_slice(&(length as u32).to_le_bytes());
+    header.extend_from_slice(&(offset as u32).to_le_bytes());
+    header
+}
+
+/// Call `send` with each frame, at `FPS`, until it fails.
+fn serve(mut send: impl FnMut(u32, &image::RgbImage, Duration) -> std::io::Result<()>) {
+    let start = Instant::now();
+    for sequence in 0.. {
+        let timestamp = monotonic_now();
+        if let Err(e) = send(sequence, &pattern(sequence), timestamp) {
+            eprintln!("stopped sending: {e}");
+            return;
+        }
+        let next = start + Duration::from_secs(1) / FPS * (sequence + 1);
+        std::thread::sleep(next.saturating_duration_since(Instant::now()));
+    }
+}
+
+fn serve_http(mut stream: TcpStream) -> std::io::Result<()> {
+    // Skip the request, we serve the same stream for any path
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    write!(
+        stream,
+        "HTTP/1.0 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={BOUNDARY}\r\n\r\n"
+    )?;
+    serve(|_, image, _| {
+        let mut jpeg = Vec::new();
+        image
+            .write_to(
+                &mut std::io::Cursor::new(&mut jpeg),
+                image::ImageFormat::Jpeg,
+            )
+            .map_err(std::io::Error::other)?;
+        write!(
+            stream,
+            "--{BOUNDARY}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+            jpeg.len()
+        )?;
+        stream.write_all(&jpeg)?;
+        stream.write_all(b"\r\n")
+    });
+    Ok(())
+}
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let [_, protocol, address] = &args[..] else {
+        eprintln!("usage: frame_server <tcp|udp|http> <address>");
+        std::process::exit(1);
+    };
+    match protocol.as_str() {
+        "tcp" => {
+            for stream in TcpListener::bind(address)?.incoming() {
+                let mut stream = stream?;
+                eprintln!("sending to {}", stream.peer_addr()?);
+                stream.set_nodelay(true)?;
+                serve(|sequence, image, timestamp| {
+                    let data = to_yuyv(image);
+                    stream.write_all(&header(sequence, timestamp, data.len(), 0))?;
+                    stream.write_all(&data)
+                });
+            }
+        }
+        "udp" => {
+            let socket = UdpSocket::bind("0.0.0.0:0")?;
+            socket.connect(address)?;
+            serve(|sequence, image, timestamp| {
+                let data = to_yuyv(image);
+                for (index, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+                    let mut datagram = header(sequence, timestamp, data.len(), index * CHUNK_SIZE);
+                    datagram.extend_from_slice(chunk);
+                    socket.send(&datagram)?;
+                }
+                Ok(())
+            });
+        }
+        "http" => {
+            for stream in TcpListener::bind(address)?.incoming() {
+                let stream = stream?;
+                eprintln!("streaming to {}", stream.peer_addr()?);
+                std::thread::spawn(move || serve_http(stream));
+            }
+        }
+        _ => {
+            eprintln!("unknown protocol {protocol}");
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
@@ -2,6 +2,9 @@
 layout(binding = 1) uniform sampler2D tex;
 layout(binding = 2) uniform Info {
 	vec2 texOffset;
+	// Width of a view in the texture, 0.5 if the views are side by side, 1.0 if there is
+	// only one view.
+	float viewWidth;
 };
 layout(location = 0) in vec4 gl_FragCoord;
 layout(location = 1) in noperspective vec3 texCoord;
@@ -12,9 +15,9 @@ void main() {
 	// shader the texCoord won't be interpolated correctly
 	// because of perspective.
 	vec2 tex_coord = texCoord.xy / texCoord.z;
-	tex_coord = tex_coord + vec2(0.25, 0.5);
+	tex_coord = tex_coord + vec2(viewWidth / 2.0, 0.5);
 
-	if (tex_coord.x < 0 || tex_coord.x > 0.5) {
+	if (tex_coord.x < 0 || tex_coord.x > viewWidth) {
 		color = vec4(0.0, 0.0, 0.0, 0.0);
 	} else {
 		tex_coord = tex_coord + texOffset;
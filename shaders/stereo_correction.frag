@@ -12,6 +12,8 @@ layout(binding = 0) uniform Parameters {
     // Pixel size of the sensor_width
     float sensorSize;
     vec2 texOffset;
+    // Width of a view in inputTex, 0.5 if there are two views side by side
+    float viewWidth;
 };
 layout(binding = 1) uniform sampler2D inputTex;
 in vec4 gl_FragCoord;
@@ -38,8 +40,8 @@ void main() {
     // move mapped so its centered at `center`
     mapped = mapped + center;
     // mapped is now 0 ~ 1
-    // scale x by 0.5 because inputTex is 2 image side by side
-    mapped.x *= 0.5;
-    // mapped is now (0~0.5, 0~1.0);
+    // scale x by the view width because inputTex might be 2 image side by side
+    mapped.x *= viewWidth;
+    // mapped is now (0~viewWidth, 0~1.0);
     outColor = texture(inputTex, mapped + texOffset);
 }
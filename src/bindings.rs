@@ -0,0 +1,269 @@
+//! Controller bindings for both VR backends, generated from the `bindings` config section.
+//!
+//! For OpenVR, this is the action manifest and the default bindings it points to. For
+//! OpenXR, these are the suggested bindings of each interaction profile.
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use xdg::BaseDirectories;
+
+use crate::{
+    config::{BindingConfig, BindingsConfig, ControllerInput, Hand},
+    vrapi::Action,
+};
+
+/// The one action set, holding all the actions.
+pub(crate) const ACTION_SET: &str = "/actions/main";
+const ACTION_MANIFEST: &str = "actions.json";
+
+#[derive(Clone, Copy, Debug)]
+enum Controller {
+    Vive,
+    Index,
+    Generic,
+}
+
+impl Controller {
+    const ALL: [Self; 3] = [Self::Vive, Self::Index, Self::Generic];
+    fn bindings(self, cfg: &BindingsConfig) -> &[BindingConfig] {
+        match self {
+            Self::Vive => &cfg.vive_controller,
+            Self::Index => &cfg.index_controller,
+            Self::Generic => &cfg.generic,
+        }
+    }
+    fn name(self) -> &'static str {
+        match self {
+            Self::Vive => "Vive Controller",
+            Self::Index => "Index Controller",
+            Self::Generic => "other controllers",
+        }
+    }
+    /// Name of the controller's option in the `bindings` config section.
+    fn option(self) -> &'static str {
+        match self {
+            Self::Vive => "vive_controller",
+            Self::Index => "index_controller",
+            Self::Generic => "generic",
+        }
+    }
+    fn openvr_controller_type(self) -> &'static str {
+        match self {
+            Self::Vive => "vive_controller",
+            Self::Index => "knuckles",
+            Self::Generic => "generic",
+        }
+    }
+    fn openxr_interaction_profile(self) -> &'static str {
+        match self {
+            Self::Vive => "/interaction_profiles/htc/vive_controller",
+            Self::Index => "/interaction_profiles/valve/index_controller",
+            Self::Generic => "/interaction_profiles/khr/simple_controller",
+        }
+    }
+    /// Path of `input`'s click in OpenXR, if the controller has it. The runtime turns
+    /// analog inputs into clicks.
+    fn openxr_input(self, input: ControllerInput) -> Option<&'static str> {
+        use ControllerInput::*;
+        Some(match (self, input) {
+            (Self::Vive | Self::Generic, Menu) => "menu/click",
+            (Self::Index, A) => "a/click",
+            (Self::Index, B) => "b/click",
+            (Self::Vive | Self::Index, Trigger) => "trigger/click",
+            (Self::Generic, Trigger) => "select/click",
+            (Self::Vive, Grip) => "squeeze/click",
+            (Self::Index, Grip) => "squeeze/value",
+            (Self::Vive, Trackpad) => "trackpad/click",
+            (Self::Index, Trackpad) => "trackpad/force",
+            (Self::Index, Thumbstick) => "thumbstick/click",
+            _ => return None,
+        })
+    }
+    /// Whether the controller has `input`. Directions of the trackpad and thumbstick
+    /// can only be bound on OpenVR, OpenXR needs an extension for them.
+    fn has_input(self, input: ControllerInput) -> bool {
+        use ControllerInput::*;
+        self.openxr_input(input).is_some()
+            || matches!(
+                (self, input),
+                (
+                    Self::Vive | Self::Index,
+                    TrackpadUp | TrackpadDown | TrackpadLeft | TrackpadRight
+                ) | (
+                    Self::Index,
+                    ThumbstickUp | ThumbstickDown | ThumbstickLeft | ThumbstickRight
+                )
+            )
+    }
+}
+
+fn hand_path(hand: Hand) -> &'static str {
+    match hand {
+        Hand::Left => "/user/hand/left",
+        Hand::Right => "/user/hand/right",
+    }
+}
+
+/// Path of `action` in the OpenVR action manifest.
+pub(crate) fn action_path(action: Action) -> String {
+    format!("{ACTION_SET}/in/{}", action.name())
+}
+
+/// Check every binding is to an input the controller has, and no input is bound twice.
+pub(crate) fn validate(cfg: &BindingsConfig) -> Result<()> {
+    for controller in Controller::ALL {
+        let bindings = controller.bindings(cfg);
+        for (i, binding) in bindings.iter().enumerate() {
+            if !controller.has_input(binding.input) {
+                return Err(anyhow!(
+                    "bindings.{}: the controller has no {:?} input",
+                    controller.option(),
+                    binding.input
+                ));
+            }
+            if bindings[..i]
+                .iter()
+                .any(|b| b.hand == binding.hand && b.input == binding.input)
+            {
+                return Err(anyhow!(
+                    "bindings.{}: {:?} {:?} is bound more than once",
+                    controller.option(),
+                    binding.hand,
+                    binding.input
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// OpenVR action manifest, pointing to the default bindings of each controller type.
+fn action_manifest() -> Value {
+    let mut localization = serde_json::Map::new();
+    localization.insert("language_tag".to_owned(), "en_US".into());
+    localization.insert(ACTION_SET.to_owned(), "Bindings".into());
+    for action in Action::ALL {
+        localization.insert(action_path(action), action.localized_name().into());
+    }
+    json!({
+        "action_sets": [{ "name": ACTION_SET, "usage": "leftright" }],
+        "actions": Action::ALL.map(|action| json!({
+            "name": action_path(action),
+            "type": "boolean",
+        })),
+        "default_bindings": Controller::ALL.map(|controller| json!({
+            "binding_url": bindings_file(controller),
+            "controller_type": controller.openvr_controller_type(),
+        })),
+        "localization": [localization],
+        "minimum_required_version": 1,
+        "version": 1,
+    })
+}
+
+fn bindings_file(controller: Controller) -> String {
+    format!("{}_bindings.json", controller.openvr_controller_type())
+}
+
+/// OpenVR default bindings of `controller`.
+fn openvr_bindings(controller: Controller, cfg: &BindingsConfig) -> Value {
+    let mut sources: Vec<Value> = Vec::new();
+    for binding in controller.bindings(cfg) {
+        use ControllerInput::*;
+        // Input, the mode it is used in, and the part of it bound
+        let (input, mode, part) = match binding.input {
+            Menu => ("application_menu", "button", "click"),
+            A => ("a", "button", "click"),
+            B => ("b", "button", "click"),
+            Trigger => ("trigger", "trigger", "click"),
+            Grip => ("grip", "button", "click"),
+            Trackpad => ("trackpad", "trackpad", "click"),
+            TrackpadUp => ("trackpad", "dpad", "north"),
+            TrackpadDown => ("trackpad", "dpad", "south"),
+            TrackpadLeft => ("trackpad", "dpad", "west"),
+            TrackpadRight => ("trackpad", "dpad", "east"),
+            Thumbstick => ("thumbstick", "joystick", "click"),
+            ThumbstickUp => ("thumbstick", "dpad", "north"),
+            ThumbstickDown => ("thumbstick", "dpad", "south"),
+            ThumbstickLeft => ("thumbstick", "dpad", "west"),
+            ThumbstickRight => ("thumbstick", "dpad", "east"),
+        };
+        let path = format!("{}/input/{input}", hand_path(binding.hand));
+        let output = json!({ "output": action_path(binding.action) });
+        // The directions of a trackpad or thumbstick are parts of one dpad
+        if let Some(source) = sources
+            .iter_mut()
+            .find(|source| source["path"] == path && source["mode"] == mode)
+        {
+            source["inputs"][part] = output;
+            continue;
+        }
+        let mut source = json!({ "mode": mode, "path": path, "inputs": { part: output } });
+        if mode == "dpad" && input == "trackpad" {
+            // Only clicking a direction counts, not touching it
+            source["parameters"] = json!({ "sub_mode": "click" });
+        }
+        sources.push(source);
+    }
+    json!({
+        "action_manifest_version": 1,
+        "alias_info": {},
+        "app_key": "system.generated.index_camera_passthrough",
+        "bindings": { ACTION_SET: { "sources": sources } },
+        "category": "steamvr_input",
+        "controller_type": controller.openvr_controller_type(),
+        "description": "",
+        "interaction_profile": "",
+        "name": format!("index_camera_passthrough bindings for {}", controller.name()),
+        "options": {},
+        "simulated_actions": [],
+    })
+}
+
+/// Write the OpenVR action manifest and default bindings into the data directory.
+/// Returns the path to the action manifest.
+pub(crate) fn write_openvr_manifest(
+    xdg: &BaseDirectories,
+    cfg: &BindingsConfig,
+) -> Result<PathBuf> {
+    let write = |name: &str, value: Value| -> Result<PathBuf> {
+        let path = xdg.place_data_file(name)?;
+        std::fs::write(&path, value.to_string())
+            .with_context(|| anyhow!("cannot write {}", path.display()))?;
+        Ok(path)
+    };
+    for controller in Controller::ALL {
+        write(&bindings_file(controller), openvr_bindings(controller, cfg))?;
+    }
+    write(ACTION_MANIFEST, action_manifest())
+}
+
+/// OpenXR suggested bindings: each interaction profile, with the actions bound in it and
+/// the paths they are bound to.
+pub(crate) fn openxr_bindings(
+    cfg: &BindingsConfig,
+) -> impl Iterator<Item = (&'static str, Vec<(Action, String)>)> + '_ {
+    Controller::ALL.into_iter().map(|controller| {
+        let bindings = controller
+            .bindings(cfg)
+            .iter()
+            .filter_map(|binding| {
+                let Some(input) = controller.openxr_input(binding.input) else {
+                    log::info!(
+                        "{:?} can't be bound on OpenXR, {:?} is left unbound on {}",
+                        binding.input,
+                        binding.action,
+                        controller.name()
+                    );
+                    return None;
+                };
+                Some((
+                    binding.action,
+                    format!("{}/input/{input}", hand_path(binding.hand)),
+                ))
+            })
+            .collect();
+        (controller.openxr_interaction_profile(), bindings)
+    })
+}
@@ -0,0 +1,590 @@
+//! Camera frame sources.
+//!
+//! The render loop doesn't care where camera frames come from, as long as they arrive in a
+//! format it knows how to handle. A [`FrameSource`] abstracts over that, the V4L2 capture
+//! device being the main implementation.
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use v4l::video::Capture;
+
+/// Format of the frames produced by a [`FrameSource`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct FrameFormat {
+    pub width: u32,
+    pub height: u32,
+    pub fourcc: v4l::FourCC,
+    /// Frames per second, 0 if the source doesn't have a fixed frame rate.
+    pub fps: u32,
+}
+
+impl std::fmt::Display for FrameFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{} {} @ {} fps",
+            self.width, self.height, self.fourcc, self.fps
+        )
+    }
+}
+
+/// A single frame borrowed from a [`FrameSource`].
+pub(crate) struct Frame<'a> {
+    pub data: &'a [u8],
+    /// When the frame was captured, in the source's own monotonic clock.
+    pub timestamp: Duration,
+    /// Sequence number of the frame, as counted by the source.
+    pub sequence: u32,
+    /// Whether `timestamp` is in `CLOCK_MONOTONIC`. Otherwise it's in a clock only the
+    /// source knows, see [`crate::clock::ClockMapper`].
+    pub monotonic: bool,
+    /// How long the frame waited between being captured and being handed to us, if the
+    /// source knows.
+    pub latency: Option<Duration>,
+    /// The source flagged the frame as possibly corrupted, e.g. because of a failed USB
+    /// transfer.
+    pub error: bool,
+}
+
+/// Counts the frames we lost or received late, to help tuning the number of buffers.
+#[derive(Clone, Copy, Default, Debug)]
+pub(crate) struct FrameStats {
+    /// frames received
+    pub frames: u64,
+    /// frames the source captured but we never got, from gaps in the sequence numbers
+    pub dropped: u64,
+    /// frames that waited more than two frame intervals before we got them
+    pub late: u64,
+    /// frames we received but didn't show, because they were broken
+    pub rejected: u64,
+    /// latency of the latest frame
+    pub latency: Option<Duration>,
+    /// highest latency since the last report
+    max_latency: Option<Duration>,
+    last_sequence: Option<u32>,
+    /// counters at the time of the last report
+    reported: (u64, u64, u64, u64),
+}
+
+impl FrameStats {
+    pub(crate) fn update(&mut self, frame: &Frame<'_>, format: &FrameFormat) {
+        self.frames += 1;
+        if let Some(last) = self.last_sequence {
+            // Sequence numbers restart from 0 when the stream is restarted, don't count
+            // that as a gap.
+            if frame.sequence > last {
+                self.dropped += u64::from(frame.sequence - last - 1);
+            }
+        }
+        self.last_sequence = Some(frame.sequence);
+        self.latency = frame.latency;
+        if let Some(latency) = frame.latency {
+            self.max_latency = Some(self.max_latency.map_or(latency, |max| max.max(latency)));
+            // Transferring the frame alone takes up to a frame interval, so only count a
+            // frame as late if a newer one could've been ready when we got it.
+            if format.fps != 0 && latency > Duration::from_secs(2) / format.fps {
+                self.late += 1;
+            }
+        }
+    }
+    /// Forget the last sequence number, e.g. when the source is reopened.
+    pub(crate) fn reset_sequence(&mut self) {
+        self.last_sequence = None;
+    }
+    /// Log the counters, at a higher level if frames were dropped, late or rejected since
+    /// the last report.
+    pub(crate) fn report(&mut self) {
+        let (frames, dropped, late, rejected) = self.reported;
+        let level = if self.dropped > dropped || self.late > late || self.rejected > rejected {
+            log::Level::Warn
+        } else {
+            log::Level::Debug
+        };
+        log::log!(
+            level,
+            "camera frames: {} received, {} dropped, {} late, {} rejected, max latency {:?}",
+            self.frames - frames,
+            self.dropped - dropped,
+            self.late - late,
+            self.rejected - rejected,
+            self.max_latency,
+        );
+        self.reported = (self.frames, self.dropped, self.late, self.rejected);
+        self.max_latency = None;
+    }
+}
+
+/// Returned by [`FrameSource::next_frame`] when no frame arrived within the timeout set
+/// by [`FrameSource::set_timeout`].
+#[derive(thiserror::Error, Debug)]
+#[error("no frame received in {0:?}")]
+pub(crate) struct Stalled(pub Duration);
+
+/// Why a frame from a [`FrameSource`] can't be shown.
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum FrameDefect {
+    #[error("frame is flagged as corrupted")]
+    Flagged,
+    #[error("frame is {0} bytes, expected {1}")]
+    Size(usize, usize),
+    #[error("frame is not a JPEG image")]
+    NotJpeg,
+    #[error("frame is cut off before the end of the JPEG image")]
+    Truncated,
+    #[error("cannot decode frame: {0:#}")]
+    Decode(anyhow::Error),
+    #[error("frame is blank")]
+    Blank,
+    #[error("frame is damaged from row {0} on")]
+    Damaged(u32),
+}
+
+/// State of the camera, as seen by the camera thread.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Status {
+    Streaming,
+    /// The camera stopped sending frames, or only sends bad ones. We are trying to
+    /// restart it, or waiting for good frames.
+    NotResponding,
+    /// The camera is gone, we are waiting for it to come back.
+    Lost,
+}
+
+pub(crate) trait FrameSource: Send {
+    /// List all the formats this source is able to produce.
+    fn supported_formats(&self) -> Result<Vec<FrameFormat>>;
+    /// Ask the source to produce frames in `requested` format. Returns the format the
+    /// source actually agreed to, which might differ from the requested one.
+    ///
+    /// Must be called before `start`.
+    fn negotiate_format(&mut self, requested: &FrameFormat) -> Result<FrameFormat>;
+    /// Start producing frames.
+    fn start(&mut self) -> Result<()>;
+    /// Block until the next frame is available.
+    fn next_frame(&mut self) -> Result<Frame<'_>>;
+    /// Stop producing frames. `start` can be called again afterwards.
+    fn stop(&mut self) -> Result<()>;
+    /// Change the camera controls, anything not set in `controls` is left alone. Sources
+    /// without controls ignore this.
+    fn set_controls(&mut self, _controls: &crate::config::CameraControls) -> Result<()> {
+        Ok(())
+    }
+    /// Make `next_frame` fail with [`Stalled`] if no frame arrives within `timeout`.
+    /// Takes effect the next time `start` is called. Sources that can't stall can
+    /// ignore this.
+    fn set_timeout(&mut self, _timeout: Option<Duration>) {}
+}
+
+/// Opens a frame source, used to open the source again after the camera is lost.
+pub(crate) type OpenSource = Box<dyn FnMut() -> Result<Box<dyn FrameSource>> + Send>;
+
+/// Pick the format to use out of the `supported` ones. Only formats matching the
+/// constraints in `cfg` are considered, among them the one with the highest resolution,
+/// then the highest frame rate is chosen. YUYV is preferred over MJPEG if both are
+/// otherwise equal, since it doesn't need to be decoded on the CPU.
+pub(crate) fn select_format(
+    supported: &[FrameFormat],
+    cfg: &crate::config::CameraConfig,
+) -> Option<FrameFormat> {
+    use crate::config::PixelFormat;
+    let yuyv = PixelFormat::YUYV.fourcc();
+    let mjpg = PixelFormat::MJPG.fourcc();
+    supported
+        .iter()
+        .filter(|format| {
+            cfg.format.map_or(
+                format.fourcc == yuyv || format.fourcc == mjpg,
+                |pixel_format| pixel_format.fourcc() == format.fourcc,
+            ) && cfg.width.map_or(true, |width| width == format.width)
+                && cfg.height.map_or(true, |height| height == format.height)
+                && cfg.fps.map_or(true, |fps| fps == format.fps)
+        })
+        .max_by_key(|format| {
+            (
+                format.width * format.height,
+                format.fps,
+                format.fourcc == yuyv,
+            )
+        })
+        .copied()
+}
+
+/// Decode a MJPEG frame into RGBA, checking it has the size we expect.
+pub(crate) fn decode_mjpeg(data: &[u8], format: &FrameFormat, output: &mut Vec<u8>) -> Result<()> {
+    let image = image::load_from_memory_with_format(data, image::ImageFormat::Jpeg)?;
+    if image.width() != format.width || image.height() != format.height {
+        return Err(anyhow!(
+            "decoded frame is {}x{}, expected {}x{}",
+            image.width(),
+            image.height(),
+            format.width,
+            format.height
+        ));
+    }
+    let image = image.into_rgba8();
+    output.clear();
+    output.extend_from_slice(image.as_raw());
+    Ok(())
+}
+
+/// Check a frame is fit to be shown, and get the pixels the pipeline takes from it:
+/// YUYV frames as they are, MJPEG frames decoded into RGBA in `decoded`.
+///
+/// Failed transfers can leave the buffer short or zeroed, which shows up as a torn or
+/// all green image, so such frames are rejected.
+pub(crate) fn validate_frame<'a>(
+    frame: &Frame<'a>,
+    format: &FrameFormat,
+    decoded: &'a mut Vec<u8>,
+) -> Result<&'a [u8], FrameDefect> {
+    if frame.error {
+        return Err(FrameDefect::Flagged);
+    }
+    if format.fourcc == crate::config::PixelFormat::MJPG.fourcc() {
+        // The size of a compressed frame varies, but it always starts with a SOI marker
+        if !frame.data.starts_with(&[0xff, 0xd8]) {
+            return Err(FrameDefect::NotJpeg);
+        }
+        // and ends with an EOI marker, unless it was cut off in transfer. Some cameras pad
+        // frames with zeros after it.
+        let end = frame
+            .data
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .map_or(0, |i| i + 1);
+        if !frame.data[..end].ends_with(&[0xff, 0xd9]) {
+            return Err(FrameDefect::Truncated);
+        }
+        decode_mjpeg(frame.data, format, decoded).map_err(FrameDefect::Decode)?;
+        // A corrupted JPEG can still decode, with everything after the damage pure green,
+        // what all zero coefficients decode to, down to the bottom of the image. Black
+        // is left alone, a dark scene decodes to that too.
+        let green = |row: &[u8]| {
+            row.chunks_exact(4)
+                .all(|pixel| pixel[0] == 0 && pixel[1] > 0 && pixel[2] == 0)
+        };
+        let row_size = format.width as usize * 4;
+        let green_rows = decoded
+            .chunks_exact(row_size)
+            .rev()
+            .take_while(|row| green(row))
+            .count() as u32;
+        if green_rows == format.height {
+            return Err(FrameDefect::Blank);
+        }
+        // JPEG images are coded in blocks of at least 8 rows
+        if green_rows >= 8 {
+            return Err(FrameDefect::Damaged(format.height - green_rows));
+        }
+        Ok(&decoded[..])
+    } else {
+        let expected = format.width as usize * format.height as usize * 2;
+        if frame.data.len() != expected {
+            return Err(FrameDefect::Size(frame.data.len(), expected));
+        }
+        // All zeros is green in YUYV, and too dark to be a real image.
+        if frame.data.iter().all(|&byte| byte == 0) {
+            return Err(FrameDefect::Blank);
+        }
+        Ok(frame.data)
+    }
+}
+
+/// V4L2 control IDs, from `linux/v4l2-controls.h`.
+mod cid {
+    const USER_BASE: u32 = 0x0098_0900;
+    const CAMERA_CLASS_BASE: u32 = 0x009a_0900;
+    pub(super) const AUTO_WHITE_BALANCE: u32 = USER_BASE + 12;
+    pub(super) const GAIN: u32 = USER_BASE + 19;
+    pub(super) const POWER_LINE_FREQUENCY: u32 = USER_BASE + 24;
+    pub(super) const WHITE_BALANCE_TEMPERATURE: u32 = USER_BASE + 26;
+    pub(super) const BACKLIGHT_COMPENSATION: u32 = USER_BASE + 28;
+    pub(super) const EXPOSURE_AUTO: u32 = CAMERA_CLASS_BASE + 1;
+    pub(super) const EXPOSURE_ABSOLUTE: u32 = CAMERA_CLASS_BASE + 2;
+}
+
+/// The V4L2 controls to set for `controls`, in the order they need to be set: automatic
+/// modes have to be turned off before the manual values can be changed.
+fn v4l_controls(controls: &crate::config::CameraControls) -> Vec<(&'static str, u32, i64)> {
+    use crate::config::{ExposureMode, PowerLineFrequency};
+    let exposure_auto = controls.exposure_auto.map(|mode| match mode {
+        ExposureMode::Auto => 0,
+        ExposureMode::Manual => 1,
+        ExposureMode::ShutterPriority => 2,
+        ExposureMode::AperturePriority => 3,
+    });
+    let power_line_frequency = controls
+        .power_line_frequency
+        .map(|frequency| match frequency {
+            PowerLineFrequency::Disabled => 0,
+            PowerLineFrequency::Hz50 => 1,
+            PowerLineFrequency::Hz60 => 2,
+            PowerLineFrequency::Auto => 3,
+        });
+    [
+        ("exposure_auto", cid::EXPOSURE_AUTO, exposure_auto),
+        ("exposure", cid::EXPOSURE_ABSOLUTE, controls.exposure),
+        ("gain", cid::GAIN, controls.gain),
+        (
+            "white_balance_auto",
+            cid::AUTO_WHITE_BALANCE,
+            controls.white_balance_auto.map(i64::from),
+        ),
+        (
+            "white_balance_temperature",
+            cid::WHITE_BALANCE_TEMPERATURE,
+            controls.white_balance_temperature,
+        ),
+        (
+            "power_line_frequency",
+            cid::POWER_LINE_FREQUENCY,
+            power_line_frequency,
+        ),
+        (
+            "backlight_compensation",
+            cid::BACKLIGHT_COMPENSATION,
+            controls.backlight_compensation,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, id, value)| Some((name, id, value?)))
+    .collect()
+}
+
+fn fraction_to_fps(interval: v4l::Fraction) -> Option<u32> {
+    (interval.numerator != 0).then(|| interval.denominator / interval.numerator)
+}
+
+fn profile_matches(device: &udev::Device, profile: &crate::config::CameraProfile) -> bool {
+    let property_matches = |name: &str, expected: &Option<String>| {
+        expected.as_ref().map_or(true, |expected| {
+            device
+                .property_value(name)
+                .is_some_and(|value| value.to_str() == Some(expected.as_str()))
+        })
+    };
+    property_matches("ID_VENDOR_ID", &profile.vendor)
+        && property_matches("ID_MODEL_ID", &profile.model)
+        && property_matches("ID_SERIAL_SHORT", &profile.serial)
+}
+
+/// Find the camera to use, and the profile that describes it. If `device` is not empty,
+/// that device is used, described by the first profile that matches it, if any.
+/// Otherwise we look for a device matching any of the `profiles`, in order.
+pub(crate) fn find_camera<'a>(
+    device: &str,
+    profiles: &'a [crate::config::CameraProfile],
+) -> Result<(std::path::PathBuf, Option<&'a crate::config::CameraProfile>)> {
+    let mut it = udev::Enumerator::new()?;
+    it.match_subsystem("video4linux")?;
+    let devices: Vec<_> = it.scan_devices()?.collect();
+    if !device.is_empty() {
+        let path = std::path::Path::new(device);
+        let profile = std::fs::canonicalize(path).ok().and_then(|path| {
+            let device = devices
+                .iter()
+                .find(|d| d.devnode().is_some_and(|devnode| devnode == path))?;
+            profiles.iter().find(|p| profile_matches(device, p))
+        });
+        return Ok((path.to_owned(), profile));
+    }
+    for profile in profiles {
+        if let Some(devnode) = devices
+            .iter()
+            .filter(|d| profile_matches(d, profile))
+            .find_map(|d| d.devnode())
+        {
+            log::info!("found camera {}: {}", profile.name, devnode.display());
+            return Ok((devnode.to_owned(), Some(profile)));
+        }
+    }
+    Err(anyhow!("No known camera found"))
+}
+
+/// A V4L2 device found on the system.
+pub(crate) struct CameraDevice {
+    pub path: std::path::PathBuf,
+    /// product name reported by the device
+    pub name: Option<String>,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    /// name of the first profile matching the device
+    pub profile: Option<String>,
+}
+
+/// List all V4L2 devices, sorted by path.
+pub(crate) fn list_cameras(profiles: &[crate::config::CameraProfile]) -> Result<Vec<CameraDevice>> {
+    let mut it = udev::Enumerator::new()?;
+    it.match_subsystem("video4linux")?;
+    let mut cameras: Vec<_> = it
+        .scan_devices()?
+        .filter_map(|device| {
+            let property = |name: &str| {
+                device
+                    .property_value(name)
+                    .and_then(|value| value.to_str())
+                    .map(str::to_owned)
+            };
+            Some(CameraDevice {
+                path: device.devnode()?.to_owned(),
+                name: property("ID_V4L_PRODUCT"),
+                vendor: property("ID_VENDOR_ID"),
+                model: property("ID_MODEL_ID"),
+                serial: property("ID_SERIAL_SHORT"),
+                profile: profiles
+                    .iter()
+                    .find(|profile| profile_matches(&device, profile))
+                    .map(|profile| profile.name.clone()),
+            })
+        })
+        .collect();
+    cameras.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(cameras)
+}
+
+/// Capture frames from a V4L2 device.
+pub(crate) struct V4lSource {
+    device: v4l::Device,
+    stream: Option<v4l::prelude::MmapStream<'static>>,
+    buffers: u32,
+    timeout: Option<Duration>,
+}
+
+impl V4lSource {
+    /// Open the device at `path`, capturing into `buffers` mmap buffers.
+    pub(crate) fn open(path: &std::path::Path, buffers: u32) -> Result<Self> {
+        let device = v4l::Device::with_path(path).context("cannot open camera device")?;
+        if !device
+            .query_caps()?
+            .capabilities
+            .contains(v4l::capability::Flags::VIDEO_CAPTURE)
+        {
+            return Err(anyhow!("Cannot capture from {}", path.display()));
+        }
+        Ok(Self {
+            device,
+            stream: None,
+            buffers: buffers.max(1),
+            timeout: None,
+        })
+    }
+}
+
+impl FrameSource for V4lSource {
+    fn supported_formats(&self) -> Result<Vec<FrameFormat>> {
+        use v4l::{frameinterval::FrameIntervalEnum, framesize::FrameSizeEnum};
+        let mut formats = Vec::new();
+        for description in self.device.enum_formats()? {
+            let fourcc = description.fourcc;
+            for framesize in self.device.enum_framesizes(fourcc)? {
+                let (width, height) = match framesize.size {
+                    FrameSizeEnum::Discrete(size) => (size.width, size.height),
+                    // Only offer the largest size of a stepwise range
+                    FrameSizeEnum::Stepwise(size) => (size.max_width, size.max_height),
+                };
+                for interval in self.device.enum_frameintervals(fourcc, width, height)? {
+                    let fps = match interval.interval {
+                        FrameIntervalEnum::Discrete(interval) => fraction_to_fps(interval),
+                        // Smallest interval is the highest frame rate
+                        FrameIntervalEnum::Stepwise(interval) => fraction_to_fps(interval.min),
+                    };
+                    if let Some(fps) = fps {
+                        formats.push(FrameFormat {
+                            width,
+                            height,
+                            fourcc,
+                            fps,
+                        });
+                    }
+                }
+            }
+        }
+        log::debug!("supported camera formats: {formats:?}");
+        Ok(formats)
+    }
+    fn negotiate_format(&mut self, requested: &FrameFormat) -> Result<FrameFormat> {
+        let format = self.device.set_format(&v4l::Format::new(
+            requested.width,
+            requested.height,
+            requested.fourcc,
+        ))?;
+        log::info!("{}", format);
+        let params = self
+            .device
+            .set_params(&v4l::video::capture::Parameters::with_fps(requested.fps))?;
+        Ok(FrameFormat {
+            width: format.width,
+            height: format.height,
+            fourcc: format.fourcc,
+            fps: fraction_to_fps(params.interval).unwrap_or(0),
+        })
+    }
+    fn start(&mut self) -> Result<()> {
+        if self.stream.is_none() {
+            // More buffers means fewer dropped frames, but the frame we get might have
+            // been sitting in the queue for longer.
+            let mut stream = v4l::prelude::MmapStream::with_buffers(
+                &self.device,
+                v4l::buffer::Type::VideoCapture,
+                self.buffers,
+            )
+            .context("cannot open camera mmap stream")?;
+            if let Some(timeout) = self.timeout {
+                stream.set_timeout(timeout);
+            }
+            self.stream = Some(stream);
+        }
+        Ok(())
+    }
+    fn next_frame(&mut self) -> Result<Frame<'_>> {
+        let stream = self
+            .stream
+            .as_mut()
+            .with_context(|| anyhow!("camera stream not started"))?;
+        let (data, metadata) = match v4l::io::traits::CaptureStream::next(stream) {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                return Err(Stalled(self.timeout.unwrap_or_default()).into())
+            }
+            Err(e) => return Err(e.into()),
+        };
+        // The buffer is as big as the largest possible frame, only part of it might've
+        // been filled.
+        let data = &data[..data.len().min(metadata.bytesused as usize)];
+        let timestamp: Duration = metadata.timestamp.into();
+        let monotonic = metadata.flags & v4l::buffer::Flags::TIMESTAMP_MASK
+            == v4l::buffer::Flags::TIMESTAMP_MONOTONIC;
+        Ok(Frame {
+            data,
+            timestamp,
+            sequence: metadata.sequence,
+            monotonic,
+            latency: monotonic
+                .then(|| crate::clock::monotonic_now().checked_sub(timestamp))
+                .flatten(),
+            error: metadata.flags.contains(v4l::buffer::Flags::ERROR),
+        })
+    }
+    fn stop(&mut self) -> Result<()> {
+        // Dropping the stream turns streaming off and releases the buffers.
+        self.stream = None;
+        Ok(())
+    }
+    fn set_controls(&mut self, controls: &crate::config::CameraControls) -> Result<()> {
+        for (name, id, value) in v4l_controls(controls) {
+            log::debug!("setting camera control {name} to {value}");
+            // Not all cameras have all the controls, so keep going if one fails.
+            if let Err(e) = self.device.set_control(v4l::control::Control {
+                id,
+                value: v4l::control::Value::Integer(value),
+            }) {
+                log::warn!("cannot set camera control {name} to {value}: {e}");
+            }
+        }
+        Ok(())
+    }
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
@@ -0,0 +1,266 @@
+//! Command line interface.
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+use xdg::BaseDirectories;
+
+use crate::{
+    camera::FrameSource,
+    config::{self, Backend, Config, DisplayMode, Eye, ProjectionMode},
+};
+
+/// show the cameras of your VR headset, or any other camera, in VR
+#[derive(FromArgs)]
+pub(crate) struct Args {
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+pub(crate) enum Command {
+    Run(RunArgs),
+    ListCameras(ListCamerasArgs),
+    ShowCalibration(ShowCalibrationArgs),
+    CheckConfig(CheckConfigArgs),
+    ShowConfig(ShowConfigArgs),
+    DumpDefaultConfig(DumpDefaultConfigArgs),
+    ConfigSchema(ConfigSchemaArgs),
+}
+
+/// run the passthrough, this is what happens if no command is given
+#[derive(FromArgs, Default)]
+#[argh(subcommand, name = "run")]
+pub(crate) struct RunArgs {
+    /// config file to use, instead of the one in the XDG config directory
+    #[argh(option, short = 'c')]
+    pub config: Option<PathBuf>,
+    /// VR runtime to use: openvr or openxr
+    #[argh(option, from_str_fn(parse_backend))]
+    pub backend: Option<Backend>,
+    /// how to show the camera: direct, flat, flat-left, flat-right, stereo,
+    /// stereo-from-camera or stereo-from-eye
+    #[argh(option, from_str_fn(parse_display_mode))]
+    pub display_mode: Option<DisplayMode>,
+    /// camera device to use for the main camera, e.g. /dev/video0
+    #[argh(option)]
+    pub camera_device: Option<String>,
+}
+
+/// list the cameras found, and the formats they can capture in
+#[derive(FromArgs)]
+#[argh(subcommand, name = "list-cameras")]
+pub(crate) struct ListCamerasArgs {
+    /// config file to take camera profiles from
+    #[argh(option, short = 'c')]
+    pub config: Option<PathBuf>,
+}
+
+/// show the calibration each camera's lens correction would use. only the Steam config
+/// files are searched, the VR runtime can't be asked without starting it.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "show-calibration")]
+pub(crate) struct ShowCalibrationArgs {
+    /// config file to use
+    #[argh(option, short = 'c')]
+    pub config: Option<PathBuf>,
+}
+
+/// check the config file is valid, without running anything
+#[derive(FromArgs)]
+#[argh(subcommand, name = "check-config")]
+pub(crate) struct CheckConfigArgs {
+    /// config file to check
+    #[argh(option, short = 'c')]
+    pub config: Option<PathBuf>,
+}
+
+/// print the config in effect, put together from the config files and the environment.
+/// options given to `run` on the command line are applied on top of this.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "show-config")]
+pub(crate) struct ShowConfigArgs {
+    /// config file to use, instead of the one in the XDG config directory
+    #[argh(option, short = 'c')]
+    pub config: Option<PathBuf>,
+}
+
+/// print the default config file, with documentation for all the options
+#[derive(FromArgs)]
+#[argh(subcommand, name = "dump-default-config")]
+pub(crate) struct DumpDefaultConfigArgs {}
+
+/// print a JSON Schema of the config file, for editors to complete and check it with
+#[derive(FromArgs)]
+#[argh(subcommand, name = "config-schema")]
+pub(crate) struct ConfigSchemaArgs {}
+
+fn parse_backend(value: &str) -> Result<Backend, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "openvr" | "steamvr" => Ok(Backend::OpenVR),
+        "openxr" => Ok(Backend::OpenXR),
+        _ => Err(format!(
+            "unknown backend {value:?}, expected openvr or openxr"
+        )),
+    }
+}
+
+fn parse_display_mode(value: &str) -> Result<DisplayMode, String> {
+    Ok(match value {
+        "direct" => DisplayMode::Direct,
+        "flat" | "flat-left" => DisplayMode::Flat { eye: Eye::Left },
+        "flat-right" => DisplayMode::Flat { eye: Eye::Right },
+        "stereo" => DisplayMode::Stereo {
+            projection_mode: Default::default(),
+        },
+        "stereo-from-camera" => DisplayMode::Stereo {
+            projection_mode: ProjectionMode::FromCamera,
+        },
+        "stereo-from-eye" => DisplayMode::Stereo {
+            projection_mode: ProjectionMode::FromEye,
+        },
+        _ => return Err(format!("unknown display mode {value:?}")),
+    })
+}
+
+impl RunArgs {
+    /// Override the options given on the command line.
+    pub(crate) fn apply(&self, cfg: &mut Config) {
+        if let Some(backend) = self.backend {
+            cfg.backend = backend;
+        }
+        if let Some(display_mode) = self.display_mode {
+            cfg.overlay.display_mode = display_mode;
+        }
+        if let Some(camera_device) = &self.camera_device {
+            cfg.camera.device.clone_from(camera_device);
+        }
+    }
+}
+
+impl Command {
+    /// Run any command other than `run`, which is handled by `main`.
+    pub(crate) fn run(self, xdg: &BaseDirectories) -> Result<()> {
+        match self {
+            Self::Run(_) => unreachable!("the passthrough is run by main"),
+            Self::ListCameras(args) => {
+                list_cameras(&config::load_config(xdg, args.config.as_deref())?)
+            }
+            Self::ShowCalibration(args) => {
+                show_calibration(&config::load_config(xdg, args.config.as_deref())?)
+            }
+            Self::CheckConfig(args) => {
+                config::load_config(xdg, args.config.as_deref())?;
+                println!("config is valid");
+                Ok(())
+            }
+            Self::ShowConfig(args) => show_config(xdg, args.config.as_deref()),
+            Self::DumpDefaultConfig(_) => {
+                print!("{}", config::DEFAULT_CONFIG);
+                Ok(())
+            }
+            Self::ConfigSchema(_) => {
+                let schema = schemars::schema_for!(Config);
+                println!("{}", serde_json::to_string_pretty(&schema)?);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn show_config(xdg: &BaseDirectories, path: Option<&Path>) -> Result<()> {
+    let files = config::ConfigFiles::find(xdg, path)?;
+    let cfg = files.load().context("invalid config")?;
+    println!("# built-in defaults, overridden by these config files in order:");
+    for file in files.iter() {
+        println!("#   {}", file.display());
+    }
+    let prefix = format!("{}_", config::ENV_PREFIX);
+    let mut vars: Vec<_> = std::env::vars()
+        .filter(|(name, _)| name.starts_with(&prefix))
+        .collect();
+    vars.sort();
+    if !vars.is_empty() {
+        println!("# then these environment variables:");
+        for (name, value) in vars {
+            println!("#   {name}={value}");
+        }
+    }
+    print!("{}", toml::to_string_pretty(&cfg)?);
+    Ok(())
+}
+
+fn list_cameras(cfg: &Config) -> Result<()> {
+    let cameras = crate::camera::list_cameras(&cfg.camera.all_profiles())?;
+    if cameras.is_empty() {
+        println!("no cameras found");
+    }
+    let unknown = || "unknown".to_owned();
+    for camera in cameras {
+        println!(
+            "{}: {}",
+            camera.path.display(),
+            camera.name.unwrap_or_else(unknown)
+        );
+        println!(
+            "  id {}:{}, serial {}",
+            camera.vendor.unwrap_or_else(unknown),
+            camera.model.unwrap_or_else(unknown),
+            camera.serial.unwrap_or_else(unknown)
+        );
+        if let Some(profile) = camera.profile {
+            println!("  matches profile {profile}");
+        }
+        let formats = crate::camera::V4lSource::open(&camera.path, 1)
+            .and_then(|source| source.supported_formats());
+        match formats {
+            Ok(formats) => {
+                for format in formats {
+                    println!("  {format}");
+                }
+            }
+            Err(e) => println!("  cannot capture: {e:#}"),
+        }
+    }
+    Ok(())
+}
+
+fn show_calibration(cfg: &Config) -> Result<()> {
+    let is_pattern = matches!(cfg.source, config::SourceConfig::Pattern { .. });
+    let main = ("main", is_pattern, &cfg.camera);
+    let extras = cfg
+        .extra_cameras
+        .iter()
+        .map(|extra| (extra.name.as_str(), false, &extra.camera));
+    for (index, (name, is_pattern, camera)) in std::iter::once(main).chain(extras).enumerate() {
+        // Same as in main, the calibration from the config is only for mono cameras
+        let layout = camera.layout.or_else(|| {
+            let profiles = camera.all_profiles();
+            let (_, profile) = crate::camera::find_camera(&camera.device, &profiles).ok()?;
+            profile.map(|profile| profile.layout)
+        });
+        let calibration = if is_pattern {
+            println!("{name} camera, calibration of the test pattern:");
+            toml::to_string_pretty(&crate::testpattern::calibration())?
+        } else if layout == Some(config::CameraLayout::Mono) {
+            let Some(calibration) = &camera.calibration else {
+                println!("{name} camera is mono, and has no calibration in the config");
+                continue;
+            };
+            println!("{name} camera, mono calibration from the config:");
+            toml::to_string_pretty(calibration)?
+        } else if index != 0 {
+            println!("{name} camera has no calibration, only the main camera is the HMD's");
+            continue;
+        } else if let Some(calibration) = crate::steam::find_steam_config() {
+            println!("{name} camera, calibration from the Steam config files:");
+            toml::to_string_pretty(&calibration)?
+        } else {
+            println!("{name} camera, no calibration in the Steam config files");
+            continue;
+        };
+        println!("{calibration}");
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+//! Mapping camera timestamps into our clock.
+//!
+//! Everything in the render loop is timed with `CLOCK_MONOTONIC`, the clock `Instant`
+//! uses on Linux, and the one OpenXR converts from with `XR_KHR_convert_timespec_time`.
+//! V4L2 usually timestamps frames in that clock already, but other sources have their own
+//! clock, which starts at an arbitrary point and might run slightly faster or slower than
+//! ours. [`ClockMapper`] estimates the relationship between the two.
+use std::{collections::VecDeque, time::Duration};
+
+/// Current time of `CLOCK_MONOTONIC`.
+pub(crate) fn monotonic_now() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid timespec, and CLOCK_MONOTONIC is always available on Linux.
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+pub(crate) fn to_timespec(time: Duration) -> libc::timespec {
+    libc::timespec {
+        tv_sec: time.as_secs() as _,
+        tv_nsec: time.subsec_nanos() as _,
+    }
+}
+
+/// Length of the window we take the lowest offset from.
+const WINDOW: Duration = Duration::from_secs(1);
+/// Number of windows the drift is estimated over.
+const MAX_SAMPLES: usize = 60;
+/// If the offset changes by more than this, the source clock jumped and we start over.
+const MAX_ERROR: f64 = 1.0;
+
+/// Estimates how a source's clock relates to `CLOCK_MONOTONIC`.
+///
+/// For every frame, the difference between when we received it and its timestamp is the
+/// clock offset plus however long the frame took to reach us. The delay is always
+/// positive, so the lowest difference within a window is the best estimate of the offset
+/// we can get. A line fitted through those estimates gives us both the offset and the
+/// drift between the clocks.
+#[derive(Default, Debug)]
+pub(crate) struct ClockMapper {
+    /// Source time all other times are relative to.
+    reference: Option<Duration>,
+    /// Lowest offset of each window, `(source time, offset)` in seconds.
+    samples: VecDeque<(f64, f64)>,
+    /// Lowest offset of the current window.
+    window: Option<(f64, f64)>,
+    /// Fitted offset at `reference`, and drift in seconds per second.
+    fit: (f64, f64),
+    last_timestamp: Duration,
+}
+
+impl ClockMapper {
+    /// Forget everything we know about the source's clock, e.g. because the source was
+    /// reopened.
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+    /// Map `timestamp` in the source's clock to `CLOCK_MONOTONIC`, given that we received
+    /// the frame at `received`.
+    pub(crate) fn map(&mut self, timestamp: Duration, received: Duration) -> Duration {
+        if timestamp < self.last_timestamp {
+            log::debug!("source clock went backwards, resetting clock mapping");
+            self.reset();
+        }
+        self.last_timestamp = timestamp;
+        let reference = *self.reference.get_or_insert(timestamp);
+        let x = (timestamp - reference).as_secs_f64();
+        let offset = received.as_secs_f64() - timestamp.as_secs_f64();
+        if self.window.is_some() && (self.predict(x) - offset).abs() > MAX_ERROR {
+            log::debug!("source clock jumped, resetting clock mapping");
+            self.reset();
+            return self.map(timestamp, received);
+        }
+
+        match &mut self.window {
+            Some((start, lowest)) if x - *start < WINDOW.as_secs_f64() => {
+                *lowest = lowest.min(offset);
+            }
+            window => {
+                if let Some(sample) = window.take() {
+                    self.samples.push_back(sample);
+                    if self.samples.len() > MAX_SAMPLES {
+                        self.samples.pop_front();
+                    }
+                }
+                *window = Some((x, offset));
+            }
+        }
+        self.update_fit();
+
+        // The frame can't have been captured after we received it.
+        let mapped = timestamp.as_secs_f64() + self.predict(x);
+        Duration::try_from_secs_f64(mapped).map_or(received, |mapped| mapped.min(received))
+    }
+    fn predict(&self, x: f64) -> f64 {
+        self.fit.0 + self.fit.1 * x
+    }
+    fn update_fit(&mut self) {
+        let (window_x, window_offset) = self.window.unwrap();
+        if self.samples.len() < 2 {
+            // Not enough data to estimate the drift yet
+            let lowest = self
+                .samples
+                .iter()
+                .map(|(_, offset)| *offset)
+                .fold(window_offset, f64::min);
+            self.fit = (lowest, 0.0);
+            return;
+        }
+        // Least squares fit of the offsets, including the current window only once it's
+        // complete, otherwise its estimate is too noisy.
+        let n = self.samples.len() as f64;
+        let mean_x = self.samples.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = self.samples.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let (sxy, sxx) = self.samples.iter().fold((0.0, 0.0), |(sxy, sxx), (x, y)| {
+            (
+                sxy + (x - mean_x) * (y - mean_y),
+                sxx + (x - mean_x) * (x - mean_x),
+            )
+        });
+        let drift = if sxx > 0.0 { sxy / sxx } else { 0.0 };
+        self.fit = (mean_y - drift * mean_x, drift);
+        log::trace!(
+            "clock offset {:.6}s, drift {:.3}ppm, current window {window_x:.3}s {window_offset:.6}s",
+            self.fit.0,
+            drift * 1e6
+        );
+    }
+}
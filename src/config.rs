@@ -1,10 +1,13 @@
 use nalgebra::{matrix, Affine3, Matrix4, TCategory};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Because your eye and the camera is at different physical locations, it is impossible
 /// to project camera view into VR space perfectly. There are trade offs approximating
 /// this projection. (viewing range means things too close to you will give you double vision).
-#[derive(Eq, PartialEq, Debug, Serialize, Deserialize, Clone, Copy, PartialOrd, Ord)]
+#[derive(
+    Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialOrd, Ord,
+)]
 pub enum ProjectionMode {
     /// in this mode, we assume your eyes are at the cameras' physical location. this mode
     /// has larger viewing range, but everything will smaller to you.
@@ -22,14 +25,14 @@ impl Default for ProjectionMode {
 pub const fn default_overlay_distance() -> f32 {
     1.0
 }
-fn serialize_transform<S>(transform: &Affine3<f32>, serializer: S) -> Result<S::Ok, S::Error>
+pub fn serialize_transform<S>(transform: &Affine3<f32>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
     let m = transform.matrix();
     m.data.0.serialize(serializer)
 }
-fn deserialize_transform<'de, D>(deserializer: D) -> Result<Affine3<f32>, D::Error>
+pub fn deserialize_transform<'de, D>(deserializer: D) -> Result<Affine3<f32>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
@@ -40,7 +43,32 @@ where
     }
     Ok(Affine3::from_matrix_unchecked(m))
 }
-#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Copy)]
+/// Schema of what `deserialize_transform` accepts: 4 columns of 4 numbers, with 0, 0, 0, 1
+/// as the last row.
+fn transform_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+    let column = |last: u32| {
+        serde_json::json!({
+            "type": "array",
+            "items": [
+                { "type": "number" },
+                { "type": "number" },
+                { "type": "number" },
+                { "const": last },
+            ],
+            "minItems": 4,
+            "maxItems": 4,
+        })
+    };
+    serde_json::from_value(serde_json::json!({
+        "type": "array",
+        "items": [column(0), column(0), column(0), column(1)],
+        "minItems": 4,
+        "maxItems": 4,
+    }))
+    .expect("valid schema")
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, JsonSchema, Clone, Copy)]
 #[serde(tag = "mode")]
 pub enum PositionMode {
     /// the overlay is shown right in front of your HMD
@@ -50,15 +78,16 @@ pub enum PositionMode {
         distance: f32,
     },
     /// the overlay will stick to a fixed position in world space, but it can be repositioned
-    /// by pressing the repositioning button
+    /// by pressing the repositioning button. where it's placed is remembered across restarts.
     Sticky {
         /// how far away from your face should the overlay be, when you reposition the overlay.
         #[serde(default = "default_overlay_distance")]
         distance: f32,
 
-        /// internal use, the position to stick to
+        /// internal use, the position to stick to. none until the overlay is placed, or
+        /// restored from where it was placed last time.
         #[serde(skip)]
-        transform: Affine3<f32>,
+        transform: Option<Affine3<f32>>,
     },
     /// the overlay is at a fixed location in space
     Absolute {
@@ -67,26 +96,36 @@ pub enum PositionMode {
             serialize_with = "serialize_transform",
             deserialize_with = "deserialize_transform"
         )]
+        #[schemars(schema_with = "transform_schema")]
         transform: Affine3<f32>,
     },
 }
 
+/// Transform of something `distance` meters right in front of the HMD.
+fn in_front_of(hmd_transform: Matrix4<f32>, distance: f32) -> Affine3<f32> {
+    let transform = hmd_transform
+        * matrix![
+            1.0, 0.0, 0.0, 0.0;
+            0.0, 1.0, 0.0, 0.0;
+            0.0, 0.0, 1.0, -distance;
+            0.0, 0.0, 0.0, 1.0;
+        ];
+    Affine3::from_matrix_unchecked(transform)
+}
+
 impl PositionMode {
     pub fn transform(&self, hmd_transform: Matrix4<f32>) -> Affine3<f32> {
         match self {
-            &PositionMode::Hmd { distance } => {
-                let transform = hmd_transform
-                    * matrix![
-                        1.0, 0.0, 0.0, 0.0;
-                        0.0, 1.0, 0.0, 0.0;
-                        0.0, 0.0, 1.0, -distance;
-                        0.0, 0.0, 0.0, 1.0;
-                    ];
-                Affine3::from_matrix_unchecked(transform)
-            }
-            &PositionMode::Absolute { transform, .. } | &PositionMode::Sticky { transform, .. } => {
-                transform
-            }
+            &PositionMode::Hmd { distance }
+            | &PositionMode::Sticky {
+                distance,
+                transform: None,
+            } => in_front_of(hmd_transform, distance),
+            &PositionMode::Absolute { transform }
+            | &PositionMode::Sticky {
+                transform: Some(transform),
+                ..
+            } => transform,
         }
     }
     pub fn reposition(&mut self, hmd_transform: Matrix4<f32>) {
@@ -95,14 +134,7 @@ impl PositionMode {
             distance,
         } = self
         {
-            let new_transform = hmd_transform
-                * matrix![
-                    1.0, 0.0, 0.0, 0.0;
-                    0.0, 1.0, 0.0, 0.0;
-                    0.0, 0.0, 1.0, -*distance;
-                    0.0, 0.0, 0.0, 1.0;
-                ];
-            *transform = Affine3::from_matrix_unchecked(new_transform);
+            *transform = Some(in_front_of(hmd_transform, *distance));
         }
     }
 }
@@ -113,7 +145,9 @@ impl Default for PositionMode {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone, Copy, PartialOrd, Ord)]
+#[derive(
+    Debug, Serialize, Deserialize, JsonSchema, Eq, PartialEq, Clone, Copy, PartialOrd, Ord,
+)]
 pub enum Eye {
     Left,
     Right,
@@ -123,7 +157,9 @@ pub const fn default_display_eye() -> Eye {
     Eye::Left
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[derive(
+    Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
 #[serde(tag = "mode")]
 pub enum DisplayMode {
     #[default]
@@ -154,25 +190,66 @@ impl DisplayMode {
     pub(crate) fn is_stereo(&self) -> bool {
         matches!(self, DisplayMode::Stereo { .. } | DisplayMode::Direct)
     }
+    /// whether the overlay texture has a different view for each eye. a mono camera only
+    /// has one view, unless it's projected for each eye.
+    pub(crate) fn texture_is_stereo(&self, mono: bool) -> bool {
+        self.is_stereo() && (!mono || self.projection_mode().is_some())
+    }
+}
+
+pub const fn default_overlay_size() -> f32 {
+    1.0
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+pub const fn default_overlay_opacity() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
 pub struct OverlayConfig {
     /// how is the overlay positioned
     #[serde(default)]
     pub position: PositionMode,
+    /// how is the camera view displayed on the overlay
+    #[serde(default)]
+    pub display_mode: DisplayMode,
+    /// width of the overlay, in meters
+    #[serde(default = "default_overlay_size")]
+    pub size: f32,
+    /// opacity of the overlay, from 0 (invisible) to 1 (opaque). on OpenXR, the runtime
+    /// needs to support XR_KHR_composition_layer_color_scale_bias for the overlay to be
+    /// see-through.
+    #[serde(default = "default_overlay_opacity")]
+    pub opacity: f32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
-pub enum Button {
-    Menu,
-    Grip,
-    Trigger,
-    A,
-    B,
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self {
+            position: Default::default(),
+            display_mode: Default::default(),
+            size: default_overlay_size(),
+            opacity: default_overlay_opacity(),
+        }
+    }
+}
+
+impl OverlayConfig {
+    fn validate(&self) -> Result<()> {
+        if !self.size.is_finite() || self.size <= 0.0 {
+            return Err(anyhow!("overlay size must be positive, not {}", self.size));
+        }
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err(anyhow!(
+                "overlay opacity must be between 0 and 1, not {}",
+                self.opacity
+            ));
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default)]
 pub enum Backend {
     #[default]
     #[serde(alias = "steamvr", alias = "openvr")]
@@ -181,39 +258,374 @@ pub enum Backend {
     OpenXR,
 }
 
-impl From<Button> for openvr_sys2::EVRButtonId {
-    fn from(b: Button) -> Self {
-        use openvr_sys2::EVRButtonId;
-        match b {
-            Button::Menu => EVRButtonId::k_EButton_ApplicationMenu,
-            Button::Grip => EVRButtonId::k_EButton_Grip,
-            Button::Trigger => EVRButtonId::k_EButton_Axis1,
-            Button::A => EVRButtonId::k_EButton_Grip,
-            Button::B => EVRButtonId::k_EButton_ApplicationMenu,
+/// pixel format of the camera image
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// uncompressed YUV 4:2:2, converted to RGB on the GPU
+    #[serde(alias = "yuyv")]
+    YUYV,
+    /// motion JPEG, decoded on the CPU. some cameras only reach their full frame rate in
+    /// this format.
+    #[serde(alias = "mjpg", alias = "MJPEG", alias = "mjpeg")]
+    MJPG,
+}
+
+impl PixelFormat {
+    pub fn fourcc(&self) -> v4l::FourCC {
+        match self {
+            PixelFormat::YUYV => v4l::FourCC::new(b"YUYV"),
+            PixelFormat::MJPG => v4l::FourCC::new(b"MJPG"),
         }
     }
 }
 
-impl PartialEq<openvr_sys2::EVRButtonId> for Button {
-    fn eq(&self, other: &openvr_sys2::EVRButtonId) -> bool {
-        &openvr_sys2::EVRButtonId::from(*self) == other
+/// exposure mode of the camera
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+pub enum ExposureMode {
+    /// automatic exposure time and iris
+    Auto,
+    /// manual exposure time and iris
+    Manual,
+    /// manual exposure time, automatic iris
+    ShutterPriority,
+    /// automatic exposure time, manual iris. this is what most webcams call "auto"
+    AperturePriority,
+}
+
+/// frequency of the mains power, to avoid flickering from lights
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+pub enum PowerLineFrequency {
+    Disabled,
+    #[serde(rename = "50Hz")]
+    Hz50,
+    #[serde(rename = "60Hz")]
+    Hz60,
+    Auto,
+}
+
+/// camera controls. anything not set is left as the camera has it.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq)]
+pub struct CameraControls {
+    /// exposure mode
+    #[serde(default)]
+    pub exposure_auto: Option<ExposureMode>,
+    /// exposure time in units of 100µs, only used if the exposure time is manual
+    #[serde(default)]
+    pub exposure: Option<i64>,
+    /// gain
+    #[serde(default)]
+    pub gain: Option<i64>,
+    /// automatic white balance
+    #[serde(default)]
+    pub white_balance_auto: Option<bool>,
+    /// white balance temperature in Kelvin, only used if automatic white balance is off
+    #[serde(default)]
+    pub white_balance_temperature: Option<i64>,
+    /// power line frequency
+    #[serde(default)]
+    pub power_line_frequency: Option<PowerLineFrequency>,
+    /// backlight compensation
+    #[serde(default)]
+    pub backlight_compensation: Option<i64>,
+}
+
+/// which part of the image auto exposure looks at
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeteringMode {
+    /// the whole image, with more weight in the middle
+    #[default]
+    CenterWeighted,
+    /// the whole image
+    Frame,
+    /// the lower half of the image, e.g. for looking at your desk
+    LowerHalf,
+}
+
+/// software auto exposure, see `[camera.auto_exposure]` in the example config
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct AutoExposureConfig {
+    /// target mean brightness, from 0 to 1
+    #[serde(default = "default_exposure_target")]
+    pub target: f32,
+    /// metering mode
+    #[serde(default)]
+    pub metering: MeteringMode,
+    /// from 0 to 1. higher values make the exposure change more slowly
+    #[serde(default = "default_exposure_damping")]
+    pub damping: f32,
+    /// shortest exposure time, in units of 100µs
+    #[serde(default = "default_min_exposure")]
+    pub min_exposure: i64,
+    /// longest exposure time, in units of 100µs. defaults to the frame interval
+    #[serde(default)]
+    pub max_exposure: Option<i64>,
+    /// highest gain to use once the exposure time is maxed out. gain is left alone if
+    /// not set
+    #[serde(default)]
+    pub max_gain: Option<i64>,
+}
+
+/// how the views are arranged in the camera image
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraLayout {
+    /// left view on the left, right view on the right
+    #[default]
+    SideBySide,
+    /// left view on top, right view at the bottom
+    TopBottom,
+    /// a single view, shown to both eyes
+    Mono,
+}
+
+/// describes a camera model, and how to find it
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct CameraProfile {
+    /// name of the profile, only used for messages
+    pub name: String,
+    /// USB vendor ID, as 4 hex digits
+    #[serde(default)]
+    pub vendor: Option<String>,
+    /// USB product ID, as 4 hex digits
+    #[serde(default)]
+    pub model: Option<String>,
+    /// serial number, to tell apart cameras of the same model
+    #[serde(default)]
+    pub serial: Option<String>,
+    /// layout of the camera image
+    #[serde(default)]
+    pub layout: CameraLayout,
+}
+
+impl CameraProfile {
+    /// the Valve Index's camera, always tried after the user's profiles
+    pub fn index() -> Self {
+        Self {
+            name: "Valve Index".to_owned(),
+            vendor: Some("28de".to_owned()),
+            model: Some("2400".to_owned()),
+            serial: None,
+            layout: CameraLayout::SideBySide,
+        }
     }
 }
 
-impl From<openvr_sys2::EVRButtonId> for Button {
-    fn from(value: openvr_sys2::EVRButtonId) -> Self {
-        use openvr_sys2::EVRButtonId;
-        match value {
-            EVRButtonId::k_EButton_ApplicationMenu => Button::Menu,
-            EVRButtonId::k_EButton_Grip => Button::Grip,
-            EVRButtonId::k_EButton_Axis1 => Button::Trigger,
-            _ => panic!("unknown button id"),
+/// calibration of a mono camera, in the same format SteamVR stores the Index's cameras in
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+pub struct MonoCalibration {
+    /// position of the camera relative to the HMD, in meters
+    pub extrinsics: crate::vrapi::Extrinsics,
+    /// lens parameters of the camera
+    pub intrinsics: crate::vrapi::Intrinsics,
+}
+
+impl From<MonoCalibration> for crate::vrapi::TrackedCamera {
+    fn from(calibration: MonoCalibration) -> Self {
+        Self {
+            extrinsics: calibration.extrinsics,
+            intrinsics: calibration.intrinsics,
+            name: crate::vrapi::Camera::Left,
         }
     }
 }
 
-pub const fn default_toggle_button() -> Button {
-    Button::Menu
+/// camera mode selection. any option that is not set is chosen automatically, preferring
+/// the highest resolution, then the highest frame rate.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct CameraConfig {
+    /// camera device to use, e.g. /dev/video0. auto detect from `profiles` if not set
+    #[serde(default)]
+    pub device: String,
+    /// width of the camera image, including both eyes
+    #[serde(default)]
+    pub width: Option<u32>,
+    /// height of the camera image
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// frame rate
+    #[serde(default)]
+    pub fps: Option<u32>,
+    /// pixel format
+    #[serde(default)]
+    pub format: Option<PixelFormat>,
+    /// number of buffers to capture into. more buffers means fewer dropped frames, but
+    /// higher latency.
+    #[serde(default = "default_camera_buffers")]
+    pub buffers: u32,
+    /// layout of the camera image, overrides the one from the camera's profile
+    #[serde(default)]
+    pub layout: Option<CameraLayout>,
+    /// cameras to look for if `device` is not set, the first one found is used
+    #[serde(default)]
+    pub profiles: Vec<CameraProfile>,
+    /// calibration of a mono camera, used for lens correction and projection. the HMD's
+    /// calibration is used for stereo cameras.
+    #[serde(default)]
+    pub calibration: Option<MonoCalibration>,
+    /// camera controls
+    #[serde(default)]
+    pub controls: CameraControls,
+    /// software auto exposure, disabled if not set. overrides the exposure and gain
+    /// controls
+    #[serde(default)]
+    pub auto_exposure: Option<AutoExposureConfig>,
+    /// restart the camera if it doesn't send a frame for this long. 0 disables the
+    /// watchdog.
+    #[serde(default = "default_watchdog_timeout", with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub watchdog_timeout: std::time::Duration,
+}
+
+impl CameraConfig {
+    /// the user's camera profiles, followed by the built-in ones
+    pub fn all_profiles(&self) -> Vec<CameraProfile> {
+        let mut profiles = self.profiles.clone();
+        profiles.push(CameraProfile::index());
+        profiles
+    }
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            device: String::new(),
+            width: None,
+            height: None,
+            fps: None,
+            format: None,
+            buffers: default_camera_buffers(),
+            layout: None,
+            profiles: Vec::new(),
+            calibration: None,
+            controls: Default::default(),
+            auto_exposure: None,
+            watchdog_timeout: default_watchdog_timeout(),
+        }
+    }
+}
+
+/// where camera frames come from
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Default)]
+#[serde(tag = "mode")]
+pub enum SourceConfig {
+    /// capture from the camera, see `camera.device`
+    #[default]
+    Camera,
+    /// play back a recording made with the `record` option
+    Replay {
+        /// path to the recording
+        path: std::path::PathBuf,
+        /// play the frames back at the pace they were recorded, instead of as fast
+        /// as possible
+        #[serde(default = "default_replay_realtime")]
+        realtime: bool,
+    },
+    /// receive a MJPEG stream over HTTP, as served by most IP cameras
+    Http {
+        /// url of the stream, only `http://` is supported
+        url: String,
+    },
+    /// receive frames sent over TCP, see `network.rs` for the framing
+    Tcp {
+        /// address to connect to, e.g. "192.168.1.10:7878"
+        address: String,
+    },
+    /// receive frames sent over UDP, see `network.rs` for the framing
+    Udp {
+        /// local address to receive on, e.g. "0.0.0.0:7878"
+        address: String,
+    },
+    /// generate stereo frames of known content, to check the lens correction and
+    /// projection without a camera
+    Pattern {
+        /// what to show
+        #[serde(default)]
+        pattern: TestPattern,
+        /// width of the frames, including both views
+        #[serde(default = "default_pattern_width")]
+        width: u32,
+        /// height of the frames
+        #[serde(default = "default_pattern_height")]
+        height: u32,
+        /// frame rate
+        #[serde(default = "default_pattern_fps")]
+        fps: u32,
+    },
+}
+
+/// content of the frames generated by the `Pattern` source
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TestPattern {
+    /// a checkerboard filling each view
+    Checkerboard,
+    /// vertical color bars in each view
+    ColorBars,
+    /// the inside of a gridded box around the HMD, as seen through the fisheye
+    /// cameras. the grid lines should come out straight after lens correction.
+    #[default]
+    Grid,
+}
+
+/// a named set of display options for the main camera. the controller can switch
+/// between profiles while running, options a profile doesn't set are left alone.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct ProfileConfig {
+    /// name of the profile, must be unique
+    pub name: String,
+    /// how the camera view is displayed, see `overlay.display_mode`
+    #[serde(default)]
+    pub display_mode: Option<DisplayMode>,
+    /// how the overlay is positioned, see `overlay.position`
+    #[serde(default)]
+    pub position: Option<PositionMode>,
+}
+
+/// a camera shown in its own overlay, in addition to the main one
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct ExtraCameraConfig {
+    /// name of the camera, used to tell the overlays apart. must be unique.
+    pub name: String,
+    /// camera mode selection
+    #[serde(default)]
+    pub camera: CameraConfig,
+    /// overlay related configuration
+    #[serde(default)]
+    pub overlay: OverlayConfig,
+}
+
+pub const fn default_replay_realtime() -> bool {
+    true
+}
+
+pub const fn default_pattern_width() -> u32 {
+    1920
+}
+
+pub const fn default_pattern_height() -> u32 {
+    960
+}
+
+pub const fn default_pattern_fps() -> u32 {
+    60
+}
+
+pub const fn default_camera_buffers() -> u32 {
+    1
+}
+
+pub const fn default_exposure_target() -> f32 {
+    0.45
+}
+
+pub const fn default_exposure_damping() -> f32 {
+    0.5
+}
+
+pub const fn default_min_exposure() -> i64 {
+    1
+}
+
+pub const fn default_watchdog_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(2)
 }
 
 pub const fn default_open_delay() -> std::time::Duration {
@@ -224,30 +636,165 @@ pub const fn default_z_order() -> u32 {
     u32::MAX
 }
 
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Eq, PartialEq, Clone, Copy)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+/// an input on a controller. not every controller has all of them.
+#[derive(
+    Debug, Serialize, Deserialize, JsonSchema, Eq, PartialEq, Clone, Copy, PartialOrd, Ord,
+)]
+pub enum ControllerInput {
+    /// the menu button, on Vive and generic controllers
+    Menu,
+    /// the A button, on Index controllers
+    A,
+    /// the B button, on Index controllers
+    B,
+    /// pulling the trigger all the way
+    Trigger,
+    /// squeezing the grip
+    Grip,
+    /// clicking the trackpad, pressing it hard on Index controllers
+    Trackpad,
+    /// clicking the top of the trackpad, only on OpenVR
+    TrackpadUp,
+    /// clicking the bottom of the trackpad, only on OpenVR
+    TrackpadDown,
+    /// clicking the left of the trackpad, only on OpenVR
+    TrackpadLeft,
+    /// clicking the right of the trackpad, only on OpenVR
+    TrackpadRight,
+    /// clicking the thumbstick, on Index controllers
+    Thumbstick,
+    /// pushing the thumbstick up, only on OpenVR
+    ThumbstickUp,
+    /// pushing the thumbstick down, only on OpenVR
+    ThumbstickDown,
+    /// pushing the thumbstick left, only on OpenVR
+    ThumbstickLeft,
+    /// pushing the thumbstick right, only on OpenVR
+    ThumbstickRight,
+}
+
+/// an action bound to an input of a controller
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Eq, PartialEq, Clone, Copy)]
+pub struct BindingConfig {
+    pub action: crate::vrapi::Action,
+    /// which hand's controller
+    pub hand: Hand,
+    pub input: ControllerInput,
+}
+
+impl BindingConfig {
+    const fn new(action: crate::vrapi::Action, hand: Hand, input: ControllerInput) -> Self {
+        Self {
+            action,
+            hand,
+            input,
+        }
+    }
+}
+
+pub fn default_vive_controller_bindings() -> Vec<BindingConfig> {
+    use crate::vrapi::Action;
+    vec![
+        BindingConfig::new(Action::Button1, Hand::Left, ControllerInput::Menu),
+        BindingConfig::new(Action::Button2, Hand::Right, ControllerInput::Menu),
+        BindingConfig::new(Action::Debug, Hand::Left, ControllerInput::Trigger),
+        BindingConfig::new(Action::Reposition, Hand::Right, ControllerInput::Trigger),
+    ]
+}
+
+pub fn default_index_controller_bindings() -> Vec<BindingConfig> {
+    use crate::vrapi::Action;
+    vec![
+        BindingConfig::new(Action::Button1, Hand::Left, ControllerInput::B),
+        BindingConfig::new(Action::Button2, Hand::Right, ControllerInput::B),
+        BindingConfig::new(Action::Debug, Hand::Left, ControllerInput::Trigger),
+        BindingConfig::new(Action::Reposition, Hand::Right, ControllerInput::A),
+    ]
+}
+
+pub fn default_generic_bindings() -> Vec<BindingConfig> {
+    use crate::vrapi::Action;
+    vec![
+        BindingConfig::new(Action::Button1, Hand::Left, ControllerInput::Menu),
+        BindingConfig::new(Action::Button2, Hand::Right, ControllerInput::Menu),
+        BindingConfig::new(Action::Debug, Hand::Left, ControllerInput::Trigger),
+        BindingConfig::new(Action::Reposition, Hand::Right, ControllerInput::Trigger),
+    ]
+}
+
+/// default controller bindings, for both OpenVR and OpenXR. on OpenVR they can still be
+/// changed in the SteamVR bindings UI, which takes precedence.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct BindingsConfig {
+    /// HTC Vive controllers
+    #[serde(default = "default_vive_controller_bindings")]
+    pub vive_controller: Vec<BindingConfig>,
+    /// Valve Index controllers
+    #[serde(default = "default_index_controller_bindings")]
+    pub index_controller: Vec<BindingConfig>,
+    /// any other controller. on OpenXR, only `Menu` and `Trigger` can be used.
+    #[serde(default = "default_generic_bindings")]
+    pub generic: Vec<BindingConfig>,
+}
+
+impl Default for BindingsConfig {
+    fn default() -> Self {
+        Self {
+            vive_controller: default_vive_controller_bindings(),
+            index_controller: default_index_controller_bindings(),
+            generic: default_generic_bindings(),
+        }
+    }
+}
+
 /// Index camera passthrough
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct Config {
+    /// version of the config file format. older files are migrated when loaded.
+    #[serde(default)]
+    pub version: u32,
     /// VR backend to use
+    #[serde(default)]
     pub backend: Backend,
-    /// camera device to use. auto detect if not set
+    /// camera mode selection
+    #[serde(default)]
+    pub camera: CameraConfig,
+    /// where camera frames come from
+    #[serde(default)]
+    pub source: SourceConfig,
+    /// more cameras, each shown in its own overlay. they are always captured from the
+    /// camera, `source` and `record` only apply to the main camera.
+    #[serde(default)]
+    pub extra_cameras: Vec<ExtraCameraConfig>,
+    /// record every raw camera frame into this file, so it can be replayed later
     #[serde(default)]
-    pub camera_device: String,
+    pub record: Option<std::path::PathBuf>,
+    /// directory snapshots are saved in. defaults to `snapshots` in the XDG data directory
+    #[serde(default)]
+    pub snapshot_dir: Option<std::path::PathBuf>,
     /// overlay related configuration
     #[serde(default)]
     pub overlay: OverlayConfig,
-    /// how is the camera view displayed on the overlay
+    /// display profiles to switch between with the "next profile" controller action,
+    /// in order
     #[serde(default)]
-    pub display_mode: DisplayMode,
-    /// which button should toggle the overlay visibility. press things
-    /// button on both controllers to toggle the overlay.
-    #[serde(default = "default_toggle_button")]
-    pub toggle_button: Button,
+    pub profiles: Vec<ProfileConfig>,
+    /// controller bindings
+    #[serde(default)]
+    pub bindings: BindingsConfig,
     /// how long does the button need to be held before the overlay open,
     /// closing the overlay is always instantaneous
     #[serde(default = "default_open_delay", with = "humantime_serde")]
+    #[schemars(with = "String")]
     pub open_delay: std::time::Duration,
     /// z order of the overlay. higher z order means the overlay is on top of
-    /// other overlays. Not supported on all backends, supported on OpenXR.
+    /// other overlays. on OpenXR, changing it needs a restart.
     #[serde(default = "default_z_order")]
     pub z_order: u32,
     /// enable debug option, including:
@@ -259,11 +806,16 @@ pub struct Config {
 impl Default for Config {
     fn default() -> Self {
         Self {
-            camera_device: "".to_owned(),
+            version: crate::migrate::CONFIG_VERSION,
+            camera: Default::default(),
+            source: Default::default(),
+            extra_cameras: Vec::new(),
+            record: None,
+            snapshot_dir: None,
             backend: Backend::OpenVR,
             overlay: Default::default(),
-            display_mode: Default::default(),
-            toggle_button: default_toggle_button(),
+            profiles: Vec::new(),
+            bindings: Default::default(),
             open_delay: std::time::Duration::ZERO,
             debug: false,
             z_order: default_z_order(),
@@ -271,13 +823,191 @@ impl Default for Config {
     }
 }
 
-use anyhow::Result;
+/// the example config file, with documentation for all the options
+pub const DEFAULT_CONFIG: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/index_camera_passthrough.toml"
+));
+
+impl Config {
+    /// check for problems deserialization can't catch
+    pub fn validate(&self) -> Result<()> {
+        self.overlay.validate()?;
+        for (i, extra) in self.extra_cameras.iter().enumerate() {
+            if extra.name == "main" || self.extra_cameras[..i].iter().any(|e| e.name == extra.name)
+            {
+                return Err(anyhow!(
+                    "camera name {:?} is used more than once",
+                    extra.name
+                ));
+            }
+            extra
+                .overlay
+                .validate()
+                .with_context(|| anyhow!("in extra camera {:?}", extra.name))?;
+        }
+        for (i, profile) in self.profiles.iter().enumerate() {
+            if self.profiles[..i].iter().any(|p| p.name == profile.name) {
+                return Err(anyhow!(
+                    "profile name {:?} is used more than once",
+                    profile.name
+                ));
+            }
+        }
+        crate::bindings::validate(&self.bindings)?;
+        Ok(())
+    }
+}
+
+use ::config::{Environment, File, FileFormat};
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
 use xdg::BaseDirectories;
-pub fn load_config(xdg: &BaseDirectories) -> Result<Config> {
-    if let Some(f) = xdg.find_config_file("index_camera_passthrough.toml") {
-        let cfg = std::fs::read_to_string(f)?;
-        Ok(toml::from_str(&cfg)?)
-    } else {
-        Ok(Default::default())
+/// Prefix of the environment variables overriding config options. nested options are
+/// separated by `__`, e.g. `INDEX_PASSTHROUGH_Z_ORDER` or `INDEX_PASSTHROUGH_CAMERA__FPS`.
+pub const ENV_PREFIX: &str = "INDEX_PASSTHROUGH";
+const CONFIG_FILE: &str = "index_camera_passthrough.toml";
+
+/// Files the config is put together from, each overriding the ones before it. After
+/// these come the environment variables, then the command line.
+#[derive(Debug, Default)]
+pub struct ConfigFiles {
+    /// system-wide config files from `$XDG_CONFIG_DIRS`, e.g. in /etc/xdg, the least
+    /// important first
+    pub system: Vec<PathBuf>,
+    /// the user's config file
+    pub user: Option<PathBuf>,
+    /// `*.toml` files in the drop-in directory next to the user's config file, named
+    /// like it but ending in `.d`, in file name order
+    pub drop_ins: Vec<PathBuf>,
+}
+
+impl ConfigFiles {
+    /// Find the config files. `path` replaces the user's config file in the XDG config
+    /// directory, and has to exist.
+    pub fn find(xdg: &BaseDirectories, path: Option<&Path>) -> Result<Self> {
+        let user_file = xdg.get_config_file(CONFIG_FILE);
+        // The system-wide directories don't have our prefix
+        let prefix = user_file
+            .parent()
+            .and_then(Path::file_name)
+            .unwrap_or_default();
+        let system = xdg
+            .get_config_dirs()
+            .into_iter()
+            .rev()
+            .map(|dir| dir.join(prefix).join(CONFIG_FILE))
+            .filter(|file| file.is_file())
+            .collect();
+        let user = match path {
+            Some(path) if !path.exists() => {
+                return Err(anyhow!("config file {} doesn't exist", path.display()))
+            }
+            Some(path) => Some(path.to_owned()),
+            None => Some(user_file).filter(|file| file.is_file()),
+        };
+        let mut drop_ins = Vec::new();
+        if let Some(dir) = user.as_ref().map(|user| user.with_extension("d")) {
+            if dir.is_dir() {
+                for entry in std::fs::read_dir(&dir)
+                    .with_context(|| anyhow!("cannot read {}", dir.display()))?
+                {
+                    let file = entry?.path();
+                    if file.extension() == Some("toml".as_ref()) {
+                        drop_ins.push(file);
+                    }
+                }
+                drop_ins.sort();
+            }
+        }
+        Ok(Self {
+            system,
+            user,
+            drop_ins,
+        })
+    }
+    /// All the files, the least important first.
+    pub fn iter(&self) -> impl Iterator<Item = &Path> {
+        self.system
+            .iter()
+            .chain(&self.user)
+            .chain(&self.drop_ins)
+            .map(PathBuf::as_path)
+    }
+    /// Rewrite the user's files written for older versions, see
+    /// [`crate::migrate::update_config_file`]. Loading them only migrates them in memory,
+    /// which is all the system-wide files get, we can't write them.
+    pub fn update(&self) -> Result<()> {
+        for file in self.user.iter().chain(&self.drop_ins) {
+            crate::migrate::update_config_file(file)?;
+        }
+        Ok(())
+    }
+    /// Merge the built-in defaults, the config files and the environment variables into
+    /// one config.
+    pub fn load(&self) -> Result<Config> {
+        let defaults = toml::to_string(&Config::default())?;
+        let mut files = Vec::new();
+        for file in self.iter() {
+            log::debug!("loading config file {}", file.display());
+            let text = crate::migrate::read_config_file(file)?;
+            if let Ok(alone) = toml::from_str::<Config>(&text) {
+                crate::migrate::warn_unknown_keys(
+                    file,
+                    &toml::Value::Table(toml::from_str(&text)?),
+                    &toml::Value::try_from(&alone)?,
+                );
+            }
+            files.push((file, text));
+        }
+        let build = |files: &[(&Path, String)], env: bool| {
+            let mut builder =
+                ::config::Config::builder().add_source(File::from_str(&defaults, FileFormat::Toml));
+            for (_, text) in files {
+                builder = builder.add_source(File::from_str(text, FileFormat::Toml));
+            }
+            if env {
+                builder = builder.add_source(
+                    Environment::with_prefix(ENV_PREFIX)
+                        .prefix_separator("_")
+                        .separator("__")
+                        .try_parsing(true),
+                );
+            }
+            builder
+                .build()
+                .and_then(|cfg| cfg.try_deserialize::<Config>())
+        };
+        let cfg = match build(&files, true) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                // Drop-in files can be partial, so a file can't be checked on its own.
+                // Adding the files one at a time finds the first that breaks the config.
+                for (i, (file, text)) in files.iter().enumerate() {
+                    let Err(e) = build(&files[..=i], false) else {
+                        continue;
+                    };
+                    // Errors in the file on its own can be pointed to a line, unlike
+                    // errors in the merged config
+                    let e = match toml::from_str::<Config>(text) {
+                        Err(alone) => anyhow::Error::from(alone),
+                        Ok(_) => e.into(),
+                    };
+                    return Err(e.context(anyhow!("invalid config file {}", file.display())));
+                }
+                return Err(anyhow::Error::from(e).context(anyhow!(
+                    "invalid config from the {ENV_PREFIX}_ environment variables"
+                )));
+            }
+        };
+        cfg.validate()?;
+        Ok(cfg)
     }
 }
+
+/// Load the config, with `path` in place of the user's config file if given.
+pub fn load_config(xdg: &BaseDirectories, path: Option<&Path>) -> Result<Config> {
+    ConfigFiles::find(xdg, path)?
+        .load()
+        .context("invalid config")
+}
@@ -38,8 +38,6 @@ use vulkano::{
     Handle, VulkanObject,
 };
 
-use crate::utils::Array;
-
 #[derive(VertexTrait, Default, Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 #[allow(non_snake_case)]
 #[repr(C)]
@@ -50,12 +48,13 @@ struct Vertex {
     inCoord: [f32; 2],
 }
 
-/// Lens correction for a stereo side-by-side image
+/// Lens correction for a stereo side-by-side image, or a single mono image
 pub struct StereoCorrection {
     device: Arc<Device>,
-    render_passes: [Arc<RenderPass>; 2],
-    pipelines: [Arc<GraphicsPipeline>; 2],
-    desc_sets: [Arc<DescriptorSet>; 2],
+    /// one for each view
+    render_passes: Vec<Arc<RenderPass>>,
+    pipelines: Vec<Arc<GraphicsPipeline>>,
+    desc_sets: Vec<Arc<DescriptorSet>>,
     /// field-of-view parameter, 0 = left eye, 1 = right eye
     fov: [[f32; 2]; 2],
 }
@@ -66,11 +65,19 @@ impl std::fmt::Debug for StereoCorrection {
             .field("device", &self.device.handle().as_raw())
             .field(
                 "render_passes",
-                &self.render_passes.each_ref().map(|x| x.handle().as_raw()),
+                &self
+                    .render_passes
+                    .iter()
+                    .map(|x| x.handle().as_raw())
+                    .collect::<Vec<_>>(),
             )
             .field(
                 "pipelines",
-                &self.pipelines.each_ref().map(|x| x.handle().as_raw()),
+                &self
+                    .pipelines
+                    .iter()
+                    .map(|x| x.handle().as_raw())
+                    .collect::<Vec<_>>(),
             )
             .field("fov", &self.fov)
             .finish_non_exhaustive()
@@ -140,7 +147,8 @@ impl StereoCorrection {
             }
         })
     }
-    /// Input size is (size * 2, size)
+    /// Input is two views side by side, each (width / 2, height), or a single view if the
+    /// calibration is for a mono camera.
     /// returns also the adjusted FOV for left and right
     ///
     /// # Arguments
@@ -153,55 +161,26 @@ impl StereoCorrection {
         allocator: Arc<dyn MemoryAllocator>,
         descriptor_set_allocator: Arc<dyn DescriptorSetAllocator>,
         input: Arc<Image>,
-        camera_calib: &crate::vrapi::StereoCamera,
+        camera_calib: &crate::vrapi::CameraCalibration,
     ) -> Result<Self> {
+        let cameras = camera_calib.views();
+        let views = cameras.len() as u32;
         let [w, h, _] = input.extent();
-        if w != h * 2 {
-            return Err(anyhow!("Input not square"));
+        if w % views != 0 {
+            return Err(anyhow!("Input is not {views} views side by side"));
         }
         let size = h as f64;
-        let center_left = [
-            camera_calib.left.intrinsics.center_x / size,
-            camera_calib.left.intrinsics.center_y / size,
-        ];
-        let center_right = [
-            camera_calib.right.intrinsics.center_x / size,
-            camera_calib.right.intrinsics.center_y / size,
-        ];
-        let focal_left = [
-            camera_calib.left.intrinsics.focal_x / size,
-            camera_calib.left.intrinsics.focal_y / size,
-        ];
-        let focal_right = [
-            camera_calib.right.intrinsics.focal_x / size,
-            camera_calib.right.intrinsics.focal_y / size,
-        ];
-        let coeff_left = camera_calib.left.intrinsics.distort.coeffs;
-        let coeff_right = camera_calib.left.intrinsics.distort.coeffs;
+        let eye_extent = [(w / views) as f32, h as f32];
         let vs = vs::load(device.clone())?;
         let fs = fs::load(device.clone())?;
-        let render_passes = [
-            vulkano::single_pass_renderpass!(device.clone(),
+        let render_passes = if views == 1 {
+            vec![vulkano::single_pass_renderpass!(device.clone(),
                 attachments: {
                     color: {
                         format: vulkano::format::Format::R8G8B8A8_UNORM,
                         samples: 1,
                         load_op: DontCare,
                         store_op: Store,
-                    }
-                },
-                pass: {
-                    color: [color],
-                    depth_stencil: {}
-                }
-            )?,
-            vulkano::single_pass_renderpass!(device.clone(),
-                attachments: {
-                    color: {
-                        format: vulkano::format::Format::R8G8B8A8_UNORM,
-                        samples: 1,
-                        load_op: Load,
-                        store_op: Store,
                         final_layout: ImageLayout::ColorAttachmentOptimal,
                     }
                 },
@@ -209,8 +188,40 @@ impl StereoCorrection {
                     color: [color],
                     depth_stencil: {}
                 }
-            )?,
-        ];
+            )?]
+        } else {
+            vec![
+                vulkano::single_pass_renderpass!(device.clone(),
+                    attachments: {
+                        color: {
+                            format: vulkano::format::Format::R8G8B8A8_UNORM,
+                            samples: 1,
+                            load_op: DontCare,
+                            store_op: Store,
+                        }
+                    },
+                    pass: {
+                        color: [color],
+                        depth_stencil: {}
+                    }
+                )?,
+                vulkano::single_pass_renderpass!(device.clone(),
+                    attachments: {
+                        color: {
+                            format: vulkano::format::Format::R8G8B8A8_UNORM,
+                            samples: 1,
+                            load_op: Load,
+                            store_op: Store,
+                            final_layout: ImageLayout::ColorAttachmentOptimal,
+                        }
+                    },
+                    pass: {
+                        color: [color],
+                        depth_stencil: {}
+                    }
+                )?,
+            ]
+        };
         let vs = vs.entry_point("main").unwrap();
         let fs = fs.entry_point("main").unwrap();
         let stages = [
@@ -222,7 +233,7 @@ impl StereoCorrection {
             PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
                 .into_pipeline_layout_create_info(device.clone())?,
         )?;
-        let pipelines = (0..2)
+        let pipelines = (0..views as usize)
             .map(|id| {
                 GraphicsPipeline::new(
                     device.clone(),
@@ -238,8 +249,8 @@ impl StereoCorrection {
                         }),
                         viewport_state: Some(ViewportState {
                             viewports: smallvec::smallvec![Viewport {
-                                offset: [size as f32 * id as f32, 0.0],
-                                extent: [size as f32, size as f32],
+                                offset: [eye_extent[0] * id as f32, 0.0],
+                                extent: eye_extent,
                                 depth_range: 0.0..=1.0,
                             }],
                             ..Default::default()
@@ -258,8 +269,7 @@ impl StereoCorrection {
                 )
                 .map_err(anyhow::Error::from)
             })
-            .collect::<Result<Array<_, 2>, _>>()?
-            .into_inner();
+            .collect::<Result<Vec<_>, _>>()?;
         let sampler = Sampler::new(
             device.clone(),
             SamplerCreateInfo {
@@ -269,24 +279,42 @@ impl StereoCorrection {
             },
         )?;
 
-        let coeffs = [
-            (0, coeff_left, center_left, focal_left),
-            (1, coeff_right, center_right, focal_right),
-        ];
+        // Calibration is in pixels of the resolution the camera was calibrated at, which
+        // might not be the resolution we are capturing at, so normalize them by that.
+        let coeffs = cameras
+            .iter()
+            .map(|camera| {
+                let intrinsics = &camera.intrinsics;
+                (
+                    intrinsics.distort.coeffs,
+                    [
+                        intrinsics.center_x / intrinsics.width,
+                        intrinsics.center_y / intrinsics.height,
+                    ],
+                    [
+                        intrinsics.focal_x / intrinsics.width,
+                        intrinsics.focal_y / intrinsics.height,
+                    ],
+                )
+            })
+            .collect::<Vec<_>>();
         let scale_fov = coeffs
-            .each_ref()
-            .map(|(_, coeff, center, focal)| Self::find_scale(coeff, center, focal));
-        // Left pass
+            .iter()
+            .map(|(coeff, center, focal)| Self::find_scale(coeff, center, focal))
+            .collect::<Vec<_>>();
+        // One pass for each view
         let desc_sets = coeffs
             .into_iter()
-            .map(|(id, coeff, center, focal)| {
+            .enumerate()
+            .map(|(id, (coeff, center, focal))| {
                 let uniform = fs::Parameters {
                     center: center.map(|x| x as f32),
                     dcoef: coeff.map(|x| x as f32),
                     focal: focal.map(|x| x as f32),
                     sensorSize: (size as f32).into(),
                     scale: [scale_fov[id][0].0, scale_fov[id][1].0],
-                    texOffset: [0.5 * id as f32, 0.0],
+                    texOffset: [id as f32 / views as f32, 0.0],
+                    viewWidth: 1.0 / views as f32,
                 };
                 let uniform = Buffer::from_data(
                     allocator.clone(),
@@ -317,9 +345,10 @@ impl StereoCorrection {
                     None,
                 )?)
             })
-            .collect::<Result<Array<_, 2>, _>>()?
-            .into_inner();
+            .collect::<Result<Vec<_>, _>>()?;
 
+        // Both eyes see the same view of a mono camera
+        let right = scale_fov.len() - 1;
         Ok(Self {
             device,
             render_passes,
@@ -327,7 +356,7 @@ impl StereoCorrection {
             desc_sets,
             fov: [
                 [scale_fov[0][0].1, scale_fov[0][1].1],
-                [scale_fov[1][0].1, scale_fov[1][1].1],
+                [scale_fov[right][0].1, scale_fov[right][1].1],
             ],
         })
     }
@@ -391,7 +420,7 @@ impl StereoCorrection {
             .cloned(),
         )
         .unwrap();
-        for id in 0..2 {
+        for id in 0..self.pipelines.len() {
             let framebuffer = Framebuffer::new(
                 self.render_passes[id].clone(),
                 FramebufferCreateInfo {
@@ -31,7 +31,14 @@ impl State {
             delay,
         }
     }
-    pub(crate) fn handle<Vr: crate::vrapi::Vr + ?Sized>(&mut self, vrsys: &Vr) -> Result<(), Vr::Error> {
+    /// Change how long the buttons need to be held to show the overlay
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+    pub(crate) fn handle<Vr: crate::vrapi::Vr + ?Sized>(
+        &mut self,
+        vrsys: &Vr,
+    ) -> Result<(), Vr::Error> {
         let mut button_pressed = 0;
         if vrsys.get_action_state(crate::vrapi::Action::Button1)? {
             button_pressed += 1;
@@ -0,0 +1,210 @@
+//! Software auto exposure.
+//!
+//! The camera's own auto exposure is tuned for tracking, not for people looking at the
+//! image, so we meter the frames ourselves and drive the manual exposure and gain controls
+//! towards a target brightness.
+use crate::{
+    camera::FrameFormat,
+    config::{AutoExposureConfig, CameraControls, CameraLayout, ExposureMode, MeteringMode},
+};
+
+/// The camera takes a couple of frames to apply new controls, don't adjust again before
+/// we can see the effect.
+const UPDATE_INTERVAL: u32 = 3;
+/// Don't bother changing anything if the brightness is this close to the target.
+const DEADBAND: f32 = 0.05;
+/// Most the exposure can change in a single step.
+const MAX_STEP: f32 = 2.0;
+/// Only meter every `SAMPLE_STEP`th pixel in each direction.
+const SAMPLE_STEP: usize = 4;
+
+/// Pixels of a frame, in one of the formats we can meter.
+pub(crate) enum Pixels<'a> {
+    Yuyv(&'a [u8]),
+    Rgba(&'a [u8]),
+}
+
+/// Luminance histogram of a frame, weighted by the metering mode.
+pub(crate) struct Histogram {
+    bins: [u64; 256],
+    total: u64,
+}
+
+impl Histogram {
+    /// Meter a frame, each view is metered the same way.
+    pub(crate) fn new(
+        pixels: Pixels<'_>,
+        extent: [u32; 2],
+        layout: CameraLayout,
+        metering: MeteringMode,
+    ) -> Self {
+        let [width, height] = extent.map(|x| x as usize);
+        let [view_width, view_height] = match layout {
+            CameraLayout::SideBySide => [width / 2, height],
+            CameraLayout::TopBottom => [width, height / 2],
+            CameraLayout::Mono => [width, height],
+        }
+        .map(|x| x.max(1));
+        let mut bins = [0; 256];
+        let mut total = 0;
+        for y in (0..height).step_by(SAMPLE_STEP) {
+            let v = (y % view_height) as f32 / view_height as f32;
+            for x in (0..width).step_by(SAMPLE_STEP) {
+                let u = (x % view_width) as f32 / view_width as f32;
+                let weight = Self::weight(metering, u, v);
+                if weight == 0 {
+                    continue;
+                }
+                let luma = match pixels {
+                    // Every other byte is the Y of a pixel
+                    Pixels::Yuyv(data) => data[(y * width + x) * 2],
+                    Pixels::Rgba(data) => {
+                        let pixel = &data[(y * width + x) * 4..][..3];
+                        ((77 * pixel[0] as u32 + 150 * pixel[1] as u32 + 29 * pixel[2] as u32) >> 8)
+                            as u8
+                    }
+                };
+                bins[luma as usize] += weight;
+                total += weight;
+            }
+        }
+        Self { bins, total }
+    }
+    /// `u` and `v` are the coordinates within a view, from 0 to 1.
+    fn weight(metering: MeteringMode, u: f32, v: f32) -> u64 {
+        match metering {
+            MeteringMode::Frame => 1,
+            MeteringMode::LowerHalf => (v >= 0.5) as u64,
+            MeteringMode::CenterWeighted => {
+                let distance2 = (u - 0.5).powi(2) + (v - 0.5).powi(2);
+                if distance2 < 0.2 * 0.2 {
+                    4
+                } else if distance2 < 0.4 * 0.4 {
+                    2
+                } else {
+                    1
+                }
+            }
+        }
+    }
+    /// Mean brightness, from 0 to 1.
+    pub(crate) fn mean(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        let sum: u64 = self
+            .bins
+            .iter()
+            .enumerate()
+            .map(|(luma, count)| luma as u64 * count)
+            .sum();
+        sum as f32 / self.total as f32 / 255.0
+    }
+}
+
+/// Closed loop controller adjusting exposure, then gain, towards the target brightness.
+pub(crate) struct AutoExposure {
+    cfg: AutoExposureConfig,
+    extent: [u32; 2],
+    layout: CameraLayout,
+    min_exposure: i64,
+    max_exposure: i64,
+    exposure: i64,
+    gain: i64,
+    frames_until_update: u32,
+}
+
+impl AutoExposure {
+    /// Start from the exposure and gain set in `controls`, if any.
+    pub(crate) fn new(
+        cfg: &AutoExposureConfig,
+        format: &FrameFormat,
+        layout: CameraLayout,
+        controls: &CameraControls,
+    ) -> Self {
+        // Exposure is in units of 100µs, and can't be longer than a frame.
+        let frame_interval = if format.fps == 0 {
+            i64::MAX
+        } else {
+            10_000 / format.fps as i64
+        };
+        let min_exposure = cfg.min_exposure.max(1);
+        let max_exposure = cfg.max_exposure.unwrap_or(frame_interval).max(min_exposure);
+        Self {
+            cfg: cfg.clone(),
+            extent: [format.width, format.height],
+            layout,
+            min_exposure,
+            max_exposure,
+            exposure: controls
+                .exposure
+                .unwrap_or(max_exposure.min(frame_interval) / 2)
+                .clamp(min_exposure, max_exposure),
+            gain: controls.gain.unwrap_or(0),
+            frames_until_update: UPDATE_INTERVAL,
+        }
+    }
+    /// The controls we want the camera to have right now.
+    pub(crate) fn controls(&self) -> CameraControls {
+        CameraControls {
+            exposure_auto: Some(ExposureMode::Manual),
+            exposure: Some(self.exposure),
+            gain: self.cfg.max_gain.map(|_| self.gain),
+            ..Default::default()
+        }
+    }
+    /// Meter a frame, returns new controls to set if they need to change.
+    pub(crate) fn update(&mut self, pixels: Pixels<'_>) -> Option<CameraControls> {
+        self.frames_until_update = self.frames_until_update.saturating_sub(1);
+        if self.frames_until_update > 0 {
+            return None;
+        }
+        let mean = Histogram::new(pixels, self.extent, self.layout, self.cfg.metering).mean();
+        let error = if mean > 0.0 {
+            self.cfg.target / mean
+        } else {
+            MAX_STEP
+        };
+        log::trace!("auto exposure: brightness {mean:.3}, error {error:.3}");
+        if (error - 1.0).abs() < DEADBAND {
+            return None;
+        }
+        let scale = error
+            .clamp(1.0 / MAX_STEP, MAX_STEP)
+            .powf(1.0 - self.cfg.damping.clamp(0.0, 0.99));
+
+        // Gain adds noise, so we only use it once the exposure is maxed out. Gain is
+        // treated as roughly linear, offset by one so we can scale up from 0.
+        let max_gain = self.cfg.max_gain.unwrap_or(self.gain).max(self.gain) as f32 + 1.0;
+        let mut exposure = self.exposure as f32;
+        let mut gain = self.gain as f32 + 1.0;
+        if self.cfg.max_gain.is_none() {
+            // The gain is left alone, all of the change goes into the exposure
+            exposure = (exposure * scale).clamp(self.min_exposure as f32, self.max_exposure as f32);
+        } else if scale > 1.0 {
+            let new_exposure = (exposure * scale).min(self.max_exposure as f32);
+            let remaining = exposure * scale / new_exposure;
+            exposure = new_exposure;
+            gain = (gain * remaining).min(max_gain);
+        } else {
+            let new_gain = (gain * scale).max(1.0);
+            let remaining = gain * scale / new_gain;
+            gain = new_gain;
+            exposure = (exposure * remaining).max(self.min_exposure as f32);
+        }
+        let exposure = exposure.round() as i64;
+        let gain = gain.round() as i64 - 1;
+        if exposure == self.exposure && gain == self.gain {
+            return None;
+        }
+        log::debug!(
+            "auto exposure: brightness {mean:.3}, exposure {} -> {exposure}, gain {} -> {gain}",
+            self.exposure,
+            self.gain
+        );
+        self.exposure = exposure;
+        self.gain = gain;
+        self.frames_until_update = UPDATE_INTERVAL;
+        Some(self.controls())
+    }
+}
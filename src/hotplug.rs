@@ -0,0 +1,25 @@
+//! Watch for cameras being plugged in, so we can recover after the camera is lost.
+use anyhow::Result;
+
+pub(crate) struct HotplugMonitor {
+    socket: udev::MonitorSocket,
+}
+
+impl HotplugMonitor {
+    pub(crate) fn new() -> Result<Self> {
+        let socket = udev::MonitorBuilder::new()?
+            .match_subsystem("video4linux")?
+            .listen()?;
+        Ok(Self { socket })
+    }
+    /// Whether any video device has been added since the last time this is called.
+    /// Never blocks.
+    pub(crate) fn device_added(&mut self) -> bool {
+        let mut added = false;
+        for event in self.socket.iter() {
+            log::debug!("udev event: {:?} {:?}", event.event_type(), event.devnode());
+            added |= event.event_type() == udev::EventType::Add;
+        }
+        added
+    }
+}
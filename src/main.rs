@@ -1,20 +1,34 @@
 #![deny(rust_2018_idioms)]
+mod bindings;
+mod camera;
+mod cli;
+mod clock;
 mod config;
 mod distortion_correction;
 mod events;
+mod exposure;
+mod hotplug;
+mod migrate;
+mod network;
 mod openvr;
 mod pipeline;
+mod placement;
 mod projection;
+mod record;
+mod snapshot;
 mod steam;
+mod testpattern;
 mod utils;
 mod vrapi;
+mod watch;
 mod yuv;
 
 use std::sync::{Arc, Mutex};
 
 use anyhow::{anyhow, Context, Result};
 
-use v4l::video::Capture;
+#[allow(unused_imports)]
+use log::info;
 use vulkano::{
     image::{AllocateImageError, Image, ImageCreateInfo, ImageUsage},
     memory::allocator::MemoryTypeFilter,
@@ -22,87 +36,39 @@ use vulkano::{
     Validated,
 };
 use xdg::BaseDirectories;
-/// Camera image will be (size * 2, size)
-const CAMERA_SIZE: u32 = 960;
-#[allow(unused_imports)]
-use log::info;
 
-use crate::{config::Backend, vrapi::VrExt};
+use crate::{camera::FrameSource, config::Backend, vrapi::VrExt};
 
 static APP_KEY: &str = "index_camera_passthrough_rs\0";
 static APP_NAME: &str = "Camera\0";
 static APP_VERSION: u32 = 0;
 
-fn find_index_camera() -> Result<std::path::PathBuf> {
-    let mut it = udev::Enumerator::new()?;
-    it.match_subsystem("video4linux")?;
-    it.match_property("ID_VENDOR_ID", "28de")?;
-    it.match_property("ID_MODEL_ID", "2400")?;
-
-    let dev = it
-        .scan_devices()?
-        .next()
-        .with_context(|| anyhow!("Index camera not found"))?;
-    let devnode = dev
-        .devnode()
-        .with_context(|| anyhow!("Index camera cannot be accessed"))?;
-    Ok(devnode.to_owned())
-}
+/// How much the size actions scale the overlay by on each press, and how far.
+const SIZE_STEP: f32 = 1.25;
+const MIN_SIZE: f32 = 0.1;
+const MAX_SIZE: f32 = 10.0;
+/// How much the opacity actions change the opacity by on each press. The overlay doesn't
+/// go more transparent than this, so it can still be found.
+const OPACITY_STEP: f32 = 0.1;
 
 static SPLASH_IMAGE: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/splash.png"));
 
 fn first_run(xdg: &BaseDirectories) -> Result<()> {
-    const ACTIONS_JSON: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/actions.json"));
-    const DATA_FILES: &[(&str, &str)] = &[
-        (
-            "vive_controller_bindings.json",
-            include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/vive_controller_bindings.json"
-            )),
-        ),
-        (
-            "knuckles_bindings.json",
-            include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/knuckles_bindings.json"
-            )),
-        ),
-        (
-            "generic_bindings.json",
-            include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/generic_bindings.json"
-            )),
-        ),
-    ];
-    const DEFAULT_CONFIG: &str = include_str!(concat!(
-        env!("CARGO_MANIFEST_DIR"),
-        "/index_camera_passthrough.toml"
-    ));
     let config = xdg.place_config_file("index_camera_passthrough.toml")?;
     if !config.exists() {
-        std::fs::write(&config, DEFAULT_CONFIG)?;
-    }
-    let actions: serde_json::Value = serde_json::from_str(ACTIONS_JSON)?;
-    std::fs::write(xdg.place_data_file("actions.json")?, actions.to_string())?;
-    for (name, data) in DATA_FILES {
-        let path = xdg.place_data_file(name)?;
-        if !path.exists() {
-            let obj: serde_json::Value = serde_json::from_str(data)?;
-            std::fs::write(&path, obj.to_string())?;
-        }
+        std::fs::write(&config, config::DEFAULT_CONFIG)?;
     }
     Ok(())
 }
 
 fn create_submittable_image(
     device: Arc<vulkano::device::Device>,
+    extent: [u32; 2],
 ) -> Result<Arc<Image>, Validated<AllocateImageError>> {
     use crate::utils::DeviceExt;
     device.new_image(
         ImageCreateInfo {
-            extent: [CAMERA_SIZE * 2, CAMERA_SIZE, 1],
+            extent: [extent[0], extent[1], 1],
             format: vulkano::format::Format::R8G8B8A8_UNORM,
             usage: ImageUsage::TRANSFER_DST
                 | ImageUsage::SAMPLED
@@ -117,15 +83,22 @@ fn create_submittable_image(
 
 struct FrameInfo {
     frame: Vec<u8>,
-    frame_time: Option<std::time::Instant>,
+    /// when the frame was captured, in `CLOCK_MONOTONIC`
+    frame_time: Option<std::time::Duration>,
     bypass_pipeline: bool,
+    /// camera frame counters, as of when this frame was received
+    stats: camera::FrameStats,
 }
 
-fn load_splash() -> Result<Vec<u8>> {
+fn load_splash(extent: [u32; 2]) -> Result<Vec<u8>> {
     log::debug!("loading splash");
-    let img = image::load_from_memory_with_format(SPLASH_IMAGE, image::ImageFormat::Png)?
-        .into_rgba8()
-        .into_raw();
+    let img = image::load_from_memory_with_format(SPLASH_IMAGE, image::ImageFormat::Png)?;
+    let img = if [img.width(), img.height()] != extent {
+        img.resize_exact(extent[0], extent[1], image::imageops::FilterType::Triangle)
+    } else {
+        img
+    };
+    let img = img.into_rgba8().into_raw();
 
     log::debug!("splash loaded");
     Ok(img)
@@ -182,140 +155,708 @@ impl AppState {
     }
 }
 
+/// Wakes up the main loop when any of the cameras has a new frame.
+struct FrameNotifier {
+    /// bumped every time there is a new frame
+    generation: Mutex<u64>,
+    notify: std::sync::Condvar,
+}
+
+impl FrameNotifier {
+    fn new() -> Self {
+        Self {
+            // Start ahead of the main loop, so it doesn't wait for the first frames
+            generation: Mutex::new(1),
+            notify: std::sync::Condvar::new(),
+        }
+    }
+    fn notify(&self) {
+        *self.generation.lock().unwrap() += 1;
+        self.notify.notify_all();
+    }
+    /// Wait until there is a notification after the `seen` one, returns the latest one.
+    fn wait(&self, seen: u64) -> u64 {
+        let generation = self.generation.lock().unwrap();
+        *self
+            .notify
+            .wait_while(generation, |generation| *generation == seen)
+            .unwrap()
+    }
+}
+
 struct CameraThread {
-    notify_new_frame: Arc<std::sync::Condvar>,
+    notifier: Arc<FrameNotifier>,
     state: Arc<AppState>,
     frame: Arc<Mutex<Option<FrameInfo>>>,
-    camera: v4l::Device,
+    source: Box<dyn FrameSource>,
+    open_source: camera::OpenSource,
+    format: camera::FrameFormat,
+    recorder: Option<record::Recorder>,
+    /// shown while the camera is lost
+    splash: Vec<u8>,
+    status: Arc<Mutex<camera::Status>>,
+    /// camera controls currently applied
+    controls: config::CameraControls,
+    /// new camera controls to apply, sent while running
+    controls_receiver: std::sync::mpsc::Receiver<config::CameraControls>,
+    auto_exposure: Option<exposure::AutoExposure>,
+    /// how long without a frame before the camera is considered stalled
+    watchdog_timeout: Option<std::time::Duration>,
 }
 
 impl CameraThread {
+    /// Replace whatever is being shown with the splash screen.
+    fn show_splash(frame: &Mutex<Option<FrameInfo>>, notifier: &FrameNotifier, splash: &[u8]) {
+        *frame.lock().unwrap() = Some(FrameInfo {
+            frame: splash.to_vec(),
+            // Newer than any frame shown so far, so it will replace the current one.
+            frame_time: Some(clock::monotonic_now()),
+            bypass_pipeline: true,
+            stats: Default::default(),
+        });
+        notifier.notify();
+    }
+    fn set_status(status: &Mutex<camera::Status>, new_status: camera::Status) {
+        let mut status = status.lock().unwrap();
+        if *status != new_status {
+            log::debug!("camera status: {:?} -> {:?}", *status, new_status);
+            *status = new_status;
+        }
+    }
+    /// Set the camera controls, auto exposure takes over the exposure and gain controls.
+    fn set_controls(
+        source: &mut dyn FrameSource,
+        controls: &config::CameraControls,
+        auto_exposure: Option<&exposure::AutoExposure>,
+    ) {
+        let auto_exposure_controls = auto_exposure.map(|ae| ae.controls());
+        for controls in std::iter::once(controls).chain(&auto_exposure_controls) {
+            if let Err(e) = source.set_controls(controls) {
+                log::error!("cannot set camera controls: {e:#}");
+            }
+        }
+    }
+    fn try_open(
+        open_source: &mut camera::OpenSource,
+        format: &camera::FrameFormat,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<Box<dyn FrameSource>> {
+        let mut source = open_source()?;
+        source.set_timeout(timeout);
+        let negotiated = source.negotiate_format(format)?;
+        if negotiated != *format {
+            // The pipeline is already set up for `format`
+            return Err(anyhow!(
+                "camera came back with format {negotiated}, expected {format}"
+            ));
+        }
+        source.start()?;
+        Ok(source)
+    }
+    /// Wait for the camera to come back, and open it again. Returns `None` if we are
+    /// asked to stop while waiting.
+    fn reopen(
+        state: &AppState,
+        open_source: &mut camera::OpenSource,
+        format: &camera::FrameFormat,
+        timeout: Option<std::time::Duration>,
+    ) -> Option<Box<dyn FrameSource>> {
+        const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+        // Retry even if we didn't see the camera being plugged in, in case we missed
+        // the event, or the device wasn't ready when we tried.
+        const RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+        let mut monitor = hotplug::HotplugMonitor::new()
+            .map_err(|e| log::warn!("cannot monitor camera hotplug: {e:#}"))
+            .ok();
+        let mut last_attempt = std::time::Instant::now();
+        loop {
+            if *state.lock() == State::Stopping {
+                return None;
+            }
+            let device_added = monitor.as_mut().is_some_and(|m| m.device_added());
+            if device_added || last_attempt.elapsed() >= RETRY_INTERVAL {
+                last_attempt = std::time::Instant::now();
+                match Self::try_open(open_source, format, timeout) {
+                    Ok(source) => return Some(source),
+                    Err(e) => log::debug!("cannot reopen camera: {e:#}"),
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
     fn run(self) -> Result<()> {
+        /// How many times we try restarting a stalled stream before reopening the camera.
+        const MAX_STREAM_RESTARTS: u32 = 3;
+        /// How often the frame counters are logged.
+        const STATS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+        /// How long the last good frame is shown for while only bad ones come, after that
+        /// the camera is not responding.
+        const MAX_BAD_FRAMES_TIME: std::time::Duration = std::time::Duration::from_secs(1);
         let Self {
-            notify_new_frame,
+            notifier,
             state,
             frame,
-            camera,
+            source,
+            mut open_source,
+            format,
+            mut recorder,
+            splash,
+            status,
+            mut controls,
+            controls_receiver,
+            mut auto_exposure,
+            watchdog_timeout,
         } = self;
 
-        let mut first_frame_time = None;
-        // We want to make the latency as low as possible, so only set a single buffer.
-        let mut video_stream =
-            v4l::prelude::MmapStream::with_buffers(&camera, v4l::buffer::Type::VideoCapture, 1)
-                .context("cannot open camera mmap stream")?;
+        let mut clock_mapper = clock::ClockMapper::default();
+        let needs_decoding = format.fourcc == config::PixelFormat::MJPG.fourcc();
+        let mut decoded = Vec::new();
+        let mut source = Some(source);
+        if let Some(source) = &mut source {
+            source.set_timeout(watchdog_timeout);
+            source.start()?;
+        }
+        let mut stalls = 0;
+        let mut stats = camera::FrameStats::default();
+        let mut last_report = std::time::Instant::now();
+        // Bad frames in a row, and when the first of them came. The last good frame keeps
+        // being shown meanwhile, for a while.
+        let mut bad_frames = 0;
+        let mut bad_since = std::time::Instant::now();
         loop {
             {
                 let guard = state.lock();
                 log::trace!("state: {:?}", *guard);
+                let was_hidden = *guard == State::Running;
                 if *state.wait_while(guard, |state| *state == State::Running) == State::Stopping {
                     break;
                 }
+                if was_hidden {
+                    // The camera kept streaming while we weren't taking its frames, those
+                    // weren't dropped
+                    stats.reset_sequence();
+                }
+            }
+            while let Ok(new_controls) = controls_receiver.try_recv() {
+                if let Some(source) = &mut source {
+                    Self::set_controls(&mut **source, &new_controls, auto_exposure.as_ref());
+                }
+                controls = new_controls;
             }
+            let Some(current_source) = source.as_mut() else {
+                log::info!("waiting for camera to come back");
+                let Some(mut new_source) =
+                    Self::reopen(&state, &mut open_source, &format, watchdog_timeout)
+                else {
+                    break;
+                };
+                log::info!("camera is back");
+                Self::set_controls(&mut *new_source, &controls, auto_exposure.as_ref());
+                stalls = 0;
+                stats.reset_sequence();
+                source = Some(new_source);
+                // The new stream might not share the old stream's clock
+                clock_mapper.reset();
+                continue;
+            };
             log::trace!("getting camera frame");
-            let (frame_data, metadata) = v4l::io::traits::CaptureStream::next(&mut video_stream)?;
-            let frame_time = if let Some((camera_reference, reference)) = first_frame_time {
-                let camera_elapsed =
-                    std::time::Duration::from(metadata.timestamp) - camera_reference;
-                reference + camera_elapsed
+            let camera_frame = match current_source.next_frame() {
+                Ok(camera_frame) => camera_frame,
+                Err(e) if e.is::<camera::Stalled>() && stalls < MAX_STREAM_RESTARTS => {
+                    stalls += 1;
+                    log::warn!("camera not responding ({e}), restarting stream, attempt {stalls}");
+                    if stalls == 1 {
+                        Self::set_status(&status, camera::Status::NotResponding);
+                        Self::show_splash(&frame, &notifier, &splash);
+                    }
+                    if let Err(e) = current_source.stop().and_then(|_| current_source.start()) {
+                        log::error!("cannot restart camera stream: {e:#}");
+                        // Try the next attempt anyway, `next_frame` will fail
+                        // immediately and we end up reopening the camera.
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    log::error!("camera lost: {e:#}");
+                    Self::set_status(&status, camera::Status::Lost);
+                    Self::show_splash(&frame, &notifier, &splash);
+                    if let Some(mut lost_source) = source.take() {
+                        if let Err(e) = lost_source.stop() {
+                            log::debug!("cannot stop lost camera: {e:#}");
+                        }
+                    }
+                    continue;
+                }
+            };
+            if stalls > 0 {
+                log::info!("camera recovered");
+                stalls = 0;
+            }
+            stats.update(&camera_frame, &format);
+            if last_report.elapsed() >= STATS_INTERVAL {
+                stats.report();
+                last_report = std::time::Instant::now();
+            }
+            let frame_time = if camera_frame.monotonic {
+                camera_frame.timestamp
             } else {
-                let now = std::time::Instant::now();
-                first_frame_time = Some((metadata.timestamp.into(), now));
-                now
+                clock_mapper.map(camera_frame.timestamp, clock::monotonic_now())
             };
             log::trace!("got camera frame {:?}", frame_time);
+            if let Some(r) = &mut recorder {
+                if let Err(e) = r.write(&camera_frame) {
+                    log::error!("cannot write to recording, recording stopped: {e:#}");
+                    recorder = None;
+                }
+            }
+            let frame_data = match camera::validate_frame(&camera_frame, &format, &mut decoded) {
+                Ok(frame_data) => frame_data,
+                Err(defect) => {
+                    if bad_frames == 0 {
+                        log::warn!("bad camera frame, keep showing the last good one: {defect}");
+                        bad_since = std::time::Instant::now();
+                    } else {
+                        log::debug!("bad camera frame: {defect}");
+                    }
+                    bad_frames += 1;
+                    stats.rejected += 1;
+                    if *status.lock().unwrap() == camera::Status::Streaming
+                        && bad_since.elapsed() >= MAX_BAD_FRAMES_TIME
+                    {
+                        // Don't leave a frozen view of the room up
+                        log::warn!("only bad camera frames for {MAX_BAD_FRAMES_TIME:?}");
+                        Self::set_status(&status, camera::Status::NotResponding);
+                        Self::show_splash(&frame, &notifier, &splash);
+                    }
+                    continue;
+                }
+            };
+            if bad_frames > 0 {
+                log::info!("camera frames are good again, after {bad_frames} bad ones");
+                bad_frames = 0;
+            }
+            Self::set_status(&status, camera::Status::Streaming);
+            let new_controls = auto_exposure.as_mut().and_then(|auto_exposure| {
+                auto_exposure.update(if needs_decoding {
+                    exposure::Pixels::Rgba(frame_data)
+                } else {
+                    exposure::Pixels::Yuyv(frame_data)
+                })
+            });
             let mut frame = frame.lock().unwrap();
             if let Some(frame) = &mut *frame {
                 frame.frame.resize(frame_data.len(), 0);
                 frame.frame.copy_from_slice(frame_data);
                 frame.frame_time = Some(frame_time);
                 frame.bypass_pipeline = false;
+                frame.stats = stats;
             } else {
                 *frame = Some(FrameInfo {
                     frame: frame_data.to_vec(),
                     frame_time: Some(frame_time),
                     bypass_pipeline: false,
+                    stats,
                 });
             }
             // log::debug!("got camera frame {}", frame_data.len());
-            notify_new_frame.notify_all();
+            notifier.notify();
+            drop(frame);
+            if let Some(new_controls) = new_controls {
+                if let Err(e) = current_source.set_controls(&new_controls) {
+                    log::warn!("cannot adjust exposure: {e:#}");
+                }
+            }
+        }
+        if let Some(source) = &mut source {
+            source.stop()?;
         }
+        stats.report();
         Ok(())
     }
 }
 
-/// Get the next camera frame. If the next frame is not available, this function
-/// will block on `wait` until it is; unless `wait` is None, in which case it
-/// will return immediately.
+/// Open the frame source, and for a camera, the profile that describes it.
+fn open_frame_source(
+    source: &config::SourceConfig,
+    camera: &config::CameraConfig,
+) -> Result<(Box<dyn FrameSource>, Option<config::CameraProfile>)> {
+    let mut profile = None;
+    let source: Box<dyn FrameSource> = match source {
+        config::SourceConfig::Camera => {
+            let profiles = camera.all_profiles();
+            let (path, found) = camera::find_camera(&camera.device, &profiles)?;
+            profile = found.cloned();
+            Box::new(camera::V4lSource::open(&path, camera.buffers)?)
+        }
+        config::SourceConfig::Replay { path, realtime } => {
+            Box::new(record::ReplaySource::open(path, *realtime)?)
+        }
+        config::SourceConfig::Http { url } => Box::new(network::HttpSource::open(url)?),
+        config::SourceConfig::Tcp { address } => Box::new(network::TcpSource::open(address)?),
+        config::SourceConfig::Udp { address } => Box::new(network::UdpSource::open(address)?),
+        config::SourceConfig::Pattern {
+            pattern,
+            width,
+            height,
+            fps,
+        } => Box::new(testpattern::PatternSource::new(
+            *pattern, *width, *height, *fps,
+        )?),
+    };
+    Ok((source, profile))
+}
+
+/// A camera from the config.
+struct CameraSpec<'a> {
+    name: &'a str,
+    source: config::SourceConfig,
+    camera: &'a config::CameraConfig,
+    overlay: &'a config::OverlayConfig,
+}
+
+/// A camera that's been opened and set up, ready to start capturing.
+struct OpenedCamera {
+    source: Box<dyn FrameSource>,
+    open_source: camera::OpenSource,
+    format: camera::FrameFormat,
+    layout: config::CameraLayout,
+    auto_exposure: Option<exposure::AutoExposure>,
+}
+
+fn open_camera(
+    name: &str,
+    source_cfg: &config::SourceConfig,
+    camera_cfg: &config::CameraConfig,
+) -> Result<OpenedCamera> {
+    let open_source: camera::OpenSource = {
+        let source = source_cfg.clone();
+        let camera = camera_cfg.clone();
+        Box::new(move || Ok(open_frame_source(&source, &camera)?.0))
+    };
+    let (mut source, profile) = open_frame_source(source_cfg, camera_cfg)?;
+    let supported_formats = source.supported_formats()?;
+    let requested_format = camera::select_format(&supported_formats, camera_cfg)
+        .with_context(|| anyhow!("no supported camera format matches {:?}", camera_cfg))?;
+    let format = source.negotiate_format(&requested_format)?;
+    log::info!("{name} camera format: {}", format);
+    source.set_controls(&camera_cfg.controls)?;
+    let is_yuyv = format.fourcc == config::PixelFormat::YUYV.fourcc();
+    let is_mjpg = format.fourcc == config::PixelFormat::MJPG.fourcc();
+    if !(is_mjpg || is_yuyv && format.width % 2 == 0) {
+        return Err(anyhow!("unsupported camera format {}", format));
+    }
+    let layout = match source_cfg {
+        config::SourceConfig::Camera => camera_cfg.layout.or(profile.map(|profile| profile.layout)),
+        // Test patterns are always side by side
+        config::SourceConfig::Pattern { .. } => Some(config::CameraLayout::SideBySide),
+        _ => camera_cfg.layout,
+    }
+    .unwrap_or_default();
+    log::info!("{name} camera layout: {layout:?}");
+    if layout == config::CameraLayout::TopBottom && format.height % 2 != 0 {
+        return Err(anyhow!("top-bottom camera image has odd height"));
+    }
+    let auto_exposure = camera_cfg
+        .auto_exposure
+        .as_ref()
+        .map(|ae| exposure::AutoExposure::new(ae, &format, layout, &camera_cfg.controls));
+    if let Some(auto_exposure) = &auto_exposure {
+        source.set_controls(&auto_exposure.controls())?;
+    }
+    Ok(OpenedCamera {
+        source,
+        open_source,
+        format,
+        layout,
+        auto_exposure,
+    })
+}
+
+/// A camera, and the overlay it's shown in.
+struct CameraView {
+    name: String,
+    /// latest frame from the camera thread
+    frame: Arc<Mutex<Option<FrameInfo>>>,
+    /// frame being shown
+    current_frame: Option<FrameInfo>,
+    status: Arc<Mutex<camera::Status>>,
+    last_status: camera::Status,
+    splash: Vec<u8>,
+    format: camera::FrameFormat,
+    layout: config::CameraLayout,
+    /// size of the pipeline's output
+    output_extent: [u32; 2],
+    display_mode: config::DisplayMode,
+    /// projection used when switching to stereo
+    projection_mode: config::ProjectionMode,
+    position: config::PositionMode,
+    /// width of the overlay, in meters
+    size: f32,
+    opacity: f32,
+    /// camera controls currently applied
+    controls: config::CameraControls,
+    controls_sender: std::sync::mpsc::Sender<config::CameraControls>,
+    thread: std::thread::JoinHandle<Result<()>>,
+}
+
+/// Get the next camera frame, or None if there is no new frame since the current one.
 fn next_camera_frame<'a>(
     shared_frame: &'_ Mutex<Option<FrameInfo>>,
     maybe_frame: &'a mut Option<FrameInfo>,
-    wait: Option<&'_ std::sync::Condvar>,
 ) -> Option<&'a mut FrameInfo> {
     let mut other_frame = shared_frame.lock().unwrap();
-    let mut frame_changed = false;
-    while !frame_changed {
-        let new_frame_elapsed = other_frame.as_ref().map(|new_frame| new_frame.frame_time);
-        let current_frame_elapsed = maybe_frame
+    let new_frame_elapsed = other_frame.as_ref().map(|new_frame| new_frame.frame_time);
+    let current_frame_elapsed = maybe_frame
+        .as_ref()
+        .map(|current_frame| current_frame.frame_time);
+    if new_frame_elapsed <= current_frame_elapsed {
+        return None;
+    }
+    std::mem::swap(maybe_frame, &mut *other_frame);
+    log::trace!("frame changed");
+    drop(other_frame);
+    maybe_frame.as_mut()
+}
+
+fn init_logging(default_filter: &str) {
+    let env = env_logger::Env::default().default_filter_or(default_filter);
+    env_logger::Builder::from_env(env)
+        .format_timestamp_millis()
+        .init();
+}
+
+/// The tracking universe overlays are placed in, `None` if it can't be told.
+fn tracking_universe(vrsys: &mut dyn vrapi::Vr<Error = anyhow::Error>) -> Option<String> {
+    vrsys.tracking_universe().unwrap_or_else(|e| {
+        log::warn!(
+            "cannot tell the tracking universe, overlay placements won't be remembered: {e:#}"
+        );
+        None
+    })
+}
+
+/// Move overlay `index` to `position`. A `Sticky` overlay goes back to where it was
+/// placed last time.
+fn set_position(
+    index: usize,
+    view: &mut CameraView,
+    position: config::PositionMode,
+    vrsys: &mut dyn vrapi::Vr<Error = anyhow::Error>,
+    placements: &placement::Placements,
+) -> Result<()> {
+    view.position = placements.restore(&view.name, position);
+    vrsys.set_position_mode(index, view.position)
+}
+
+/// Apply the size and opacity of overlay `index`.
+fn set_appearance(
+    index: usize,
+    view: &CameraView,
+    vrsys: &mut dyn vrapi::Vr<Error = anyhow::Error>,
+) -> Result<()> {
+    vrsys.set_size(index, view.size)?;
+    if !vrsys.set_opacity(index, view.opacity)? {
+        log::warn!(
+            "the {} overlay cannot be see-through with this VR runtime",
+            view.name
+        );
+    }
+    Ok(())
+}
+
+/// The display mode after `mode`, when cycling through them with the controller: flat
+/// with the left camera, flat with the right camera, then stereo with `projection_mode`.
+/// A mono camera has no right camera to show.
+fn next_display_mode(
+    mode: config::DisplayMode,
+    projection_mode: config::ProjectionMode,
+    mono: bool,
+) -> config::DisplayMode {
+    use config::{DisplayMode, Eye};
+    match mode {
+        DisplayMode::Flat { eye: Eye::Left } if !mono => DisplayMode::Flat { eye: Eye::Right },
+        DisplayMode::Flat { .. } => DisplayMode::Stereo { projection_mode },
+        DisplayMode::Stereo { .. } | DisplayMode::Direct => DisplayMode::Flat { eye: Eye::Left },
+    }
+}
+
+/// Save the frame each camera is showing, in the background.
+fn take_snapshots(views: &[CameraView], dir: &std::path::Path) {
+    for view in views {
+        // Nothing to save if the camera isn't showing an image of its own
+        let Some(frame) = view
+            .current_frame
             .as_ref()
-            .map(|current_frame| current_frame.frame_time);
-        frame_changed = new_frame_elapsed > current_frame_elapsed;
-        if frame_changed {
-            std::mem::swap(maybe_frame, &mut *other_frame);
-            log::trace!("frame changed");
-        } else if let Some(wait) = wait {
-            other_frame = wait.wait(other_frame).unwrap();
+            .filter(|frame| !frame.bypass_pipeline)
+        else {
+            log::warn!("no image from the {} camera to save", view.name);
+            continue;
+        };
+        let frame = frame.frame.clone();
+        let (dir, name, format) = (dir.to_owned(), view.name.clone(), view.format);
+        std::thread::spawn(move || match snapshot::save(&dir, &name, &format, &frame) {
+            Ok(path) => log::info!("saved {name} camera image to {}", path.display()),
+            Err(e) => log::error!("cannot save {name} camera image: {e:#}"),
+        });
+    }
+}
+
+/// Switch the main camera's overlay to `profile`.
+fn apply_profile(
+    profile: &config::ProfileConfig,
+    view: &mut CameraView,
+    vrsys: &mut dyn vrapi::Vr<Error = anyhow::Error>,
+    placements: &placement::Placements,
+) -> Result<()> {
+    log::info!("switching to profile {}", profile.name);
+    if let Some(display_mode) = profile.display_mode {
+        // Applied when the next frame is shown
+        view.display_mode = display_mode;
+        view.projection_mode = display_mode
+            .projection_mode()
+            .unwrap_or(view.projection_mode);
+    }
+    if let Some(position) = profile.position {
+        set_position(0, view, position, vrsys, placements)?;
+    }
+    Ok(())
+}
+
+/// Load the config again, and apply the changes that can be applied while running.
+/// An invalid config is logged and otherwise ignored.
+fn reload_config(
+    xdg: &BaseDirectories,
+    run_args: &cli::RunArgs,
+    cfg: &mut config::Config,
+    views: &mut [CameraView],
+    vrsys: &mut dyn vrapi::Vr<Error = anyhow::Error>,
+    placements: &placement::Placements,
+    ui_state: &mut events::State,
+) -> Result<()> {
+    let mut new_cfg = match config::load_config(xdg, run_args.config.as_deref()) {
+        Ok(new_cfg) => new_cfg,
+        Err(e) => {
+            log::error!("config change rejected: {e:#}");
+            return Ok(());
+        }
+    };
+    // Options from the command line still take precedence
+    run_args.apply(&mut new_cfg);
+
+    if new_cfg.open_delay != cfg.open_delay {
+        log::info!("open delay changed: {:?}", new_cfg.open_delay);
+        ui_state.set_delay(new_cfg.open_delay);
+    }
+    if new_cfg.z_order != cfg.z_order {
+        log::info!("z order changed: {}", new_cfg.z_order);
+        if !vrsys.set_z_order(new_cfg.z_order)? {
+            log::warn!("z order change will take effect after a restart");
+        }
+    }
+    /// Settings of the camera shown in overlay `index`, named `name`.
+    fn camera_settings<'a>(
+        cfg: &'a config::Config,
+        index: usize,
+        name: &str,
+    ) -> Option<(&'a config::CameraConfig, &'a config::OverlayConfig)> {
+        if index == 0 {
+            Some((&cfg.camera, &cfg.overlay))
         } else {
-            break;
+            cfg.extra_cameras
+                .iter()
+                .find(|extra| extra.name == name)
+                .map(|extra| (&extra.camera, &extra.overlay))
         }
     }
-    drop(other_frame);
-    if maybe_frame.is_none() {
-        // This should never happen, because we should at least have the splash screen
-        log::error!("No frame");
+    for (index, view) in views.iter_mut().enumerate() {
+        let Some((camera, overlay)) = camera_settings(&new_cfg, index, &view.name) else {
+            continue;
+        };
+        // Display mode and position are compared with the old config rather than what's
+        // shown, so they don't undo switching profiles unless they are edited
+        let old = camera_settings(cfg, index, &view.name).map(|(_, old)| old);
+        if old.map(|old| old.display_mode) != Some(overlay.display_mode) {
+            log::info!(
+                "{} display mode changed: {:?}",
+                view.name,
+                overlay.display_mode
+            );
+            // Applied when the next frame is shown
+            view.display_mode = overlay.display_mode;
+            view.projection_mode = overlay
+                .display_mode
+                .projection_mode()
+                .unwrap_or(view.projection_mode);
+        }
+        if old.map(|old| (old.size, old.opacity)) != Some((overlay.size, overlay.opacity)) {
+            log::info!(
+                "{} overlay size or opacity changed: {}m, {}",
+                view.name,
+                overlay.size,
+                overlay.opacity
+            );
+            view.size = overlay.size;
+            view.opacity = overlay.opacity;
+            set_appearance(index, view, vrsys)?;
+        }
+        if old.map(|old| old.position) != Some(overlay.position) {
+            log::info!(
+                "{} overlay position changed: {:?}",
+                view.name,
+                overlay.position
+            );
+            set_position(index, view, overlay.position, vrsys, placements)?;
+        }
+        if camera.controls != view.controls {
+            log::info!(
+                "{} camera controls changed: {:?}",
+                view.name,
+                camera.controls
+            );
+            view.controls = camera.controls.clone();
+            // The camera thread only goes away when we are stopping
+            view.controls_sender.send(view.controls.clone()).ok();
+        }
     }
-    if frame_changed {
-        Some(maybe_frame.as_mut().unwrap())
-    } else {
-        None
+
+    // Anything else needs the cameras, or the VR backend, to be set up again
+    let mut applied = new_cfg.clone();
+    applied.camera.controls.clone_from(&cfg.camera.controls);
+    applied.overlay.clone_from(&cfg.overlay);
+    applied.open_delay = cfg.open_delay;
+    applied.z_order = cfg.z_order;
+    for extra in &mut applied.extra_cameras {
+        if let Some(old) = cfg.extra_cameras.iter().find(|old| old.name == extra.name) {
+            extra.camera.controls.clone_from(&old.camera.controls);
+            extra.overlay.clone_from(&old.overlay);
+        }
+    }
+    if toml::Value::try_from(&applied)? != toml::Value::try_from(&*cfg)? {
+        log::warn!("some config changes will take effect after a restart");
     }
+    *cfg = new_cfg;
+    Ok(())
 }
 
 fn main() -> Result<()> {
     let xdg = xdg::BaseDirectories::with_prefix("index_camera_passthrough")?;
+    let args: cli::Args = argh::from_env();
+    let run_args = match args.command {
+        None => Default::default(),
+        Some(cli::Command::Run(run_args)) => run_args,
+        Some(command) => {
+            init_logging("warn");
+            return command.run(&xdg);
+        }
+    };
     first_run(&xdg)?;
 
-    let cfg = config::load_config(&xdg)?;
-    let env =
-        env_logger::Env::default().default_filter_or(if cfg.debug { "debug" } else { "info" });
-    env_logger::Builder::from_env(env)
-        .format_timestamp_millis()
-        .init();
-    let camera = v4l::Device::with_path(if cfg.camera_device.is_empty() {
-        find_index_camera()?
-    } else {
-        std::path::Path::new(&cfg.camera_device).to_owned()
-    })
-    .context("cannot open camera device")?;
-    if !camera
-        .query_caps()?
-        .capabilities
-        .contains(v4l::capability::Flags::VIDEO_CAPTURE)
-    {
-        return Err(anyhow!("Cannot capture from index camera"));
-    }
-    let format = camera.set_format(&v4l::Format::new(
-        CAMERA_SIZE * 2,
-        CAMERA_SIZE,
-        v4l::FourCC::new(b"YUYV"),
-    ))?;
-    log::info!("{}", format);
-    camera.set_params(&v4l::video::capture::Parameters::with_fps(54))?;
-    let splash = load_splash()?;
-    let frame = Arc::new(Mutex::new(Some(FrameInfo {
-        frame: splash.clone(),
-        frame_time: None,
-        bypass_pipeline: true,
-    })));
+    let config_files = config::ConfigFiles::find(&xdg, run_args.config.as_deref())?;
+    config_files.update().context("invalid config")?;
+    let mut cfg = config_files.load().context("invalid config")?;
+    run_args.apply(&mut cfg);
+    init_logging(if cfg.debug { "debug" } else { "info" });
+    log::debug!("config in effect:\n{}", toml::to_string_pretty(&cfg)?);
 
     let app_state = Arc::new(AppState::new());
     let state2 = app_state.clone();
@@ -325,40 +866,179 @@ fn main() -> Result<()> {
     })
     .expect("Error setting Ctrl-C handler");
 
-    let notify_new_frame = Arc::new(std::sync::Condvar::new());
-    let camera_thread = CameraThread {
-        notify_new_frame: notify_new_frame.clone(),
-        frame: frame.clone(),
-        state: app_state.clone(),
-        camera,
-    };
-    let camera_thread = std::thread::spawn(move || camera_thread.run());
+    // The main camera, followed by the extra ones. Each is shown in its own overlay.
+    let mut cameras = vec![CameraSpec {
+        name: "main",
+        source: cfg.source.clone(),
+        camera: &cfg.camera,
+        overlay: &cfg.overlay,
+    }];
+    for extra in &cfg.extra_cameras {
+        cameras.push(CameraSpec {
+            name: &extra.name,
+            source: config::SourceConfig::Camera,
+            camera: &extra.camera,
+            overlay: &extra.overlay,
+        });
+    }
+
+    let notifier = Arc::new(FrameNotifier::new());
+    let mut views = Vec::new();
+    let mut overlays = Vec::new();
+    for (index, spec) in cameras.iter().enumerate() {
+        let OpenedCamera {
+            source,
+            open_source,
+            format,
+            layout,
+            auto_exposure,
+        } = open_camera(spec.name, &spec.source, spec.camera)
+            .with_context(|| anyhow!("cannot open {} camera", spec.name))?;
+        // Size of the overlay texture, with the views side by side
+        let overlay_extent =
+            pipeline::Pipeline::stereo_extent(layout, [format.width, format.height]);
+        // Size of the pipeline's output, a mono image is kept as a single view
+        let output_extent =
+            pipeline::Pipeline::output_extent(layout, [format.width, format.height]);
+        // Only the main camera is recorded
+        let recorder = cfg
+            .record
+            .as_deref()
+            .filter(|_| index == 0)
+            .map(|path| record::Recorder::create(path, &format))
+            .transpose()?;
+        let splash = load_splash(output_extent)?;
+        let frame = Arc::new(Mutex::new(Some(FrameInfo {
+            frame: splash.clone(),
+            frame_time: None,
+            bypass_pipeline: true,
+            stats: Default::default(),
+        })));
+        let status = Arc::new(Mutex::new(camera::Status::Streaming));
+        let (controls_sender, controls_receiver) = std::sync::mpsc::channel();
+        let camera_thread = CameraThread {
+            notifier: notifier.clone(),
+            frame: frame.clone(),
+            state: app_state.clone(),
+            source,
+            open_source,
+            format,
+            recorder,
+            splash: splash.clone(),
+            status: status.clone(),
+            controls: spec.camera.controls.clone(),
+            controls_receiver,
+            auto_exposure,
+            watchdog_timeout: (!spec.camera.watchdog_timeout.is_zero())
+                .then_some(spec.camera.watchdog_timeout),
+        };
+        let thread = std::thread::spawn(move || camera_thread.run());
+        overlays.push(vrapi::OverlayInfo {
+            name: spec.name.to_owned(),
+            extent: overlay_extent,
+            mono: layout == config::CameraLayout::Mono,
+            // The HMD's calibration is for the HMD's camera, which is the main one
+            hmd_camera: index == 0,
+        });
+        views.push(CameraView {
+            name: spec.name.to_owned(),
+            frame,
+            current_frame: None,
+            status,
+            last_status: camera::Status::Streaming,
+            splash,
+            format,
+            layout,
+            output_extent,
+            display_mode: spec.overlay.display_mode,
+            projection_mode: spec
+                .overlay
+                .display_mode
+                .projection_mode()
+                .unwrap_or_default(),
+            position: spec.overlay.position,
+            size: spec.overlay.size,
+            opacity: spec.overlay.opacity,
+            controls: spec.camera.controls.clone(),
+            controls_sender,
+            thread,
+        });
+    }
 
     log::info!("{:?}", cfg.backend);
     let mut vrsys = match cfg.backend {
-        Backend::OpenVR => crate::vrapi::OpenVr::new(&xdg)?.boxed(),
-        Backend::OpenXR => crate::vrapi::OpenXr::new(cfg.z_order)?.boxed(),
+        Backend::OpenVR => {
+            let action_manifest = bindings::write_openvr_manifest(&xdg, &cfg.bindings)?;
+            crate::vrapi::OpenVr::new(&action_manifest, &overlays)?.boxed()
+        }
+        Backend::OpenXR => {
+            crate::vrapi::OpenXr::new(cfg.z_order, &overlays, &cfg.bindings)?.boxed()
+        }
     };
+    if !vrsys.set_z_order(cfg.z_order)? {
+        log::warn!("cannot set z order {}", cfg.z_order);
+    }
     let instance = vrsys.vk_instance();
     let (device, queue) = vrsys.vk_device(&instance);
 
-    // Create a VROverlay
-    vrsys.set_display_mode(config::DisplayMode::Direct)?;
-    // load camera config
-    let camera_config = if let Some(cfg) = vrsys.load_camera_paramter() {
-        Some(cfg)
-    } else if let Some(cfg) = steam::find_steam_config() {
-        // if the backend doesn't give us the parameters, we try
-        // to search in the steam config for whatever that looks
-        // like a camera parameter file
-        vrsys.set_fallback_camera_config(cfg);
-        Some(cfg)
-    } else {
-        log::warn!("No camera parameters found");
-        None
-    };
+    let mut placements = placement::Placements::load(&xdg);
+    placements.set_universe(tracking_universe(&mut *vrsys));
+    let mut pipelines = Vec::new();
+    for (index, ((view, overlay), spec)) in
+        views.iter_mut().zip(&overlays).zip(&cameras).enumerate()
+    {
+        // Create a VROverlay
+        vrsys.set_display_mode(index, config::DisplayMode::Direct)?;
+        // load camera config
+        let camera_config = if overlay.mono {
+            // The HMD's camera calibration doesn't apply to a mono camera, it has to come
+            // from our config
+            let calibration = spec
+                .camera
+                .calibration
+                .map(|calibration| vrapi::CameraCalibration::Mono(calibration.into()));
+            match calibration {
+                Some(calibration) => vrsys.set_fallback_camera_config(index, calibration),
+                None => log::warn!("No camera parameters for the {} camera", view.name),
+            }
+            calibration
+        } else if let config::SourceConfig::Pattern { .. } = spec.source {
+            // The lens correction has to undo the distortion the pattern is drawn with
+            let cfg = vrapi::CameraCalibration::Stereo(testpattern::calibration());
+            vrsys.set_fallback_camera_config(index, cfg);
+            Some(cfg)
+        } else if let Some(cfg) = vrsys.load_camera_paramter(index) {
+            Some(cfg)
+        } else if let Some(cfg) = steam::find_steam_config().filter(|_| overlay.hmd_camera) {
+            // if the backend doesn't give us the parameters, we try
+            // to search in the steam config for whatever that looks
+            // like a camera parameter file
+            let cfg = vrapi::CameraCalibration::Stereo(cfg);
+            vrsys.set_fallback_camera_config(index, cfg);
+            Some(cfg)
+        } else {
+            log::warn!("No camera parameters found for the {} camera", view.name);
+            None
+        };
+
+        let position = view.position;
+        set_position(index, view, position, &mut *vrsys, &placements)?;
+        set_appearance(index, view, &mut *vrsys)?;
 
-    vrsys.set_position_mode(cfg.overlay.position)?;
+        // MJPEG frames are decoded into RGBA by the camera thread
+        let need_yuv_conversion = view.format.fourcc == config::PixelFormat::YUYV.fourcc();
+        let pipeline = pipeline::Pipeline::new(
+            device.clone(),
+            vrsys.vk_allocator(),
+            vrsys.vk_descriptor_set_allocator(),
+            need_yuv_conversion,
+            camera_config,
+            [view.format.width, view.format.height],
+            view.layout,
+        )?;
+        log::debug!("{} pipeline: {pipeline:?}", view.name);
+        pipelines.push(pipeline);
+    }
 
     // Show overlay
     log::debug!("showing overlay");
@@ -368,66 +1048,80 @@ fn main() -> Result<()> {
     vrsys.wait_for_ready()?;
     log::debug!("VR runtime ready");
 
-    // TODO: don't hardcode this
-    struct AppConfig {
-        need_yuv_conversion: bool,
-    }
-    let config = AppConfig {
-        need_yuv_conversion: true,
-    };
-
-    let mut pipeline = pipeline::Pipeline::new(
-        device.clone(),
-        vrsys.vk_allocator(),
-        vrsys.vk_descriptor_set_allocator(),
-        config.need_yuv_conversion,
-        camera_config,
-    )?;
-
-    log::debug!("pipeline: {pipeline:?}");
-
+    // Watch the user's config file, to apply changes while running
+    let mut config_watcher = config::ConfigFiles::find(&xdg, run_args.config.as_deref())
+        .ok()
+        .and_then(|files| files.user)
+        .and_then(|path| {
+            watch::ConfigWatcher::new(&path)
+                .map_err(|e| log::warn!("cannot watch config file: {e:#}"))
+                .ok()
+        });
     let mut ui_state = events::State::new(cfg.open_delay);
-    let mut debug_pressed = false;
-    let mut maybe_current_frame: Option<FrameInfo> = None;
+    // Whether each action was active last time, the ones that happen once per press are
+    // only triggered when they become active
+    let mut pressed = [false; vrapi::Action::ALL.len()];
+    // Index into `cfg.profiles`, none until the user switches to a profile
+    let mut active_profile: Option<usize> = None;
+    let mut seen_frames = 0;
     let is_synchronized = vrsys.is_synchronized();
     loop {
-        let next_frame = if ui_state.is_visible() {
-            // Try to get the next camera frame if the overlay is visible
-            next_camera_frame(
-                &frame,
-                &mut maybe_current_frame,
-                if is_synchronized {
-                    // Don't wait if we are obliged to synchronize with the VR runtime
-                    None
-                } else {
-                    Some(&notify_new_frame)
-                },
-            )
-        } else {
-            None
-        };
+        if ui_state.is_visible() && !is_synchronized {
+            // Wait for any of the cameras to have a new frame, unless we are obliged to
+            // synchronize with the VR runtime
+            seen_frames = notifier.wait(seen_frames);
+        }
+
+        let mut has_frame = false;
+        for (index, (view, pipeline)) in views.iter_mut().zip(&mut pipelines).enumerate() {
+            let current_camera_status = *view.status.lock().unwrap();
+            if current_camera_status != view.last_status {
+                // The camera thread replaces the camera image with the splash screen, which
+                // serves as the indicator in the overlay.
+                match current_camera_status {
+                    camera::Status::Streaming => log::info!("{} camera is streaming", view.name),
+                    camera::Status::NotResponding => {
+                        log::warn!("{} camera not responding", view.name)
+                    }
+                    camera::Status::Lost => log::warn!("{} camera not available", view.name),
+                }
+                view.last_status = current_camera_status;
+            }
 
-        if let Some(current_frame) = next_frame {
+            // Try to get the next camera frame if the overlay is visible
+            if !ui_state.is_visible() {
+                continue;
+            }
+            let Some(current_frame) = next_camera_frame(&view.frame, &mut view.current_frame)
+            else {
+                continue;
+            };
+            has_frame = true;
             // We try to get the pose at the time when the camera frame is captured. GetDeviceToAbsoluteTrackingPose
             // doesn't specifically say if a negative time offset will work...
             // also, do this as early as possible.
-            let elapsed = current_frame
+            let capture_time = current_frame
                 .frame_time
-                .map(|frame_time| std::time::Instant::now() - frame_time)
-                .unwrap_or_default();
-            log::trace!("elapsed: {elapsed:?}");
+                .unwrap_or_else(clock::monotonic_now);
+            log::trace!(
+                "{} elapsed: {:?}, camera: {:?}",
+                view.name,
+                clock::monotonic_now().saturating_sub(capture_time),
+                current_frame.stats
+            );
             // Allocate final image
             log::trace!("frame bypass pipeline: {}", current_frame.bypass_pipeline);
             // Display mode must be known before we call `get_render_texture`.
             if current_frame.bypass_pipeline {
-                vrsys.set_display_mode(config::DisplayMode::Direct)?;
+                vrsys.set_display_mode(index, config::DisplayMode::Direct)?;
             } else {
-                vrsys.set_display_mode(cfg.display_mode)?;
+                vrsys.set_display_mode(index, view.display_mode)?;
             }
-            if let Some(output) = vrsys.get_render_texture()? {
+            if let Some(output) = vrsys.get_render_texture(index)? {
                 if current_frame.bypass_pipeline {
                     let future = pipeline.submit_cpu_image(
                         &current_frame.frame,
+                        view.output_extent,
                         vrsys.vk_command_buffer_allocator(),
                         &queue,
                         output,
@@ -448,12 +1142,27 @@ fn main() -> Result<()> {
                 }
 
                 // Submit the texture
-                vrsys.submit_texture(elapsed, &pipeline.fov())?;
+                vrsys.submit_texture(index, capture_time, &pipeline.fov())?;
+
+                // Remember where a `Sticky` overlay has been placed
+                let position = vrsys.position_mode(index);
+                if position != view.position {
+                    view.position = position;
+                    if let config::PositionMode::Sticky {
+                        transform: Some(transform),
+                        ..
+                    } = position
+                    {
+                        placements.set(&view.name, transform);
+                    }
+                }
             }
-        } else {
+        }
+        if has_frame {
+            vrsys.end_frame()?;
+        } else if !ui_state.is_visible() || is_synchronized {
             // If we don't have a frame, this means either the overlay is not visible, or
             // the VR runtime is a synchronized runtime so we didn't block wait for the frame.
-            assert!(!ui_state.is_visible() || is_synchronized);
             vrsys.refresh()?;
         }
 
@@ -466,6 +1175,9 @@ fn main() -> Result<()> {
                     app_state.stop();
                     vrsys.acknowledge_quit();
                 }
+                vrapi::Event::TrackingUniverseChanged => {
+                    placements.set_universe(tracking_universe(&mut *vrsys));
+                }
             }
         }
 
@@ -474,44 +1186,141 @@ fn main() -> Result<()> {
             break;
         }
 
+        if config_watcher
+            .as_mut()
+            .is_some_and(|watcher| watcher.changed())
+        {
+            log::info!("config file changed");
+            reload_config(
+                &xdg,
+                &run_args,
+                &mut cfg,
+                &mut views,
+                &mut *vrsys,
+                &placements,
+                &mut ui_state,
+            )?;
+        }
+
         // Handle user inputs
         vrsys.update_action_state()?;
-        if vrsys.get_action_state(vrapi::Action::Debug)? {
-            if !debug_pressed {
-                log::debug!("Capture next frame");
+        let was_pressed = pressed;
+        for action in vrapi::Action::ALL {
+            pressed[action as usize] = vrsys.get_action_state(action)?;
+        }
+        let just_pressed =
+            |action: vrapi::Action| pressed[action as usize] && !was_pressed[action as usize];
+        if just_pressed(vrapi::Action::Debug) {
+            log::debug!("Capture next frame");
+            for pipeline in &mut pipelines {
                 pipeline.capture_next_frame();
-                debug_pressed = true;
             }
-        } else {
-            debug_pressed = false;
         }
         ui_state.handle(&*vrsys)?;
         match ui_state.turn() {
             events::Action::ShowOverlay => {
                 log::debug!("showing overlay");
+                // Pick up changes to the config, in case we missed them
+                reload_config(
+                    &xdg,
+                    &run_args,
+                    &mut cfg,
+                    &mut views,
+                    &mut *vrsys,
+                    &placements,
+                    &mut ui_state,
+                )?;
                 vrsys.show_overlay()?;
-                {
-                    let mut other_frame = frame.lock().unwrap();
+                for view in &views {
+                    let mut other_frame = view.frame.lock().unwrap();
                     *other_frame = Some(FrameInfo {
-                        frame: splash.clone(),
+                        frame: view.splash.clone(),
                         frame_time: None,
                         bypass_pipeline: true,
+                        stats: Default::default(),
                     });
                 }
+                notifier.notify();
                 app_state.start_capture();
             }
             events::Action::HideOverlay => {
                 log::debug!("hiding overlay");
                 vrsys.hide_overlay()?;
-                maybe_current_frame = None;
+                for view in &mut views {
+                    view.current_frame = None;
+                }
                 app_state.stop_capture();
             }
             _ => (),
         }
-        if vrsys.get_action_state(vrapi::Action::Reposition)? {
-            vrsys.set_position_mode(cfg.overlay.position)?;
+        if pressed[vrapi::Action::Reposition as usize] {
+            for (index, view) in views.iter_mut().enumerate() {
+                // Place `Sticky` overlays in front of the HMD again
+                if let config::PositionMode::Sticky { transform, .. } = &mut view.position {
+                    *transform = None;
+                }
+                vrsys.set_position_mode(index, view.position)?;
+            }
+        } else if let Err(e) = placements.save() {
+            // Only saved once the overlays are let go, not on every frame they are moved
+            log::warn!("cannot save overlay placements: {e:#}");
+        }
+        // Input goes to the game as well, so these only act while the overlay is shown,
+        // not to change it behind the user's back
+        let visible = ui_state.is_visible();
+        let shown_pressed = |action: vrapi::Action| visible && just_pressed(action);
+        if shown_pressed(vrapi::Action::NextProfile) && !cfg.profiles.is_empty() {
+            let next = active_profile.map_or(0, |active| (active + 1) % cfg.profiles.len());
+            apply_profile(&cfg.profiles[next], &mut views[0], &mut *vrsys, &placements)?;
+            active_profile = Some(next);
+        }
+        // The display actions, like profiles, are for the main camera
+        let main = &mut views[0];
+        if shown_pressed(vrapi::Action::CycleDisplayMode) {
+            let mono = main.layout == config::CameraLayout::Mono;
+            // Applied when the next frame is shown
+            main.display_mode = next_display_mode(main.display_mode, main.projection_mode, mono);
+            log::info!("display mode: {:?}", main.display_mode);
+        }
+        if shown_pressed(vrapi::Action::ToggleProjectionMode) {
+            main.projection_mode = match main.projection_mode {
+                config::ProjectionMode::FromCamera => config::ProjectionMode::FromEye,
+                config::ProjectionMode::FromEye => config::ProjectionMode::FromCamera,
+            };
+            log::info!("projection mode: {:?}", main.projection_mode);
+            if let config::DisplayMode::Stereo { projection_mode } = &mut main.display_mode {
+                *projection_mode = main.projection_mode;
+            }
+        }
+        // Only a press changes the size or opacity, and never away from where it's going,
+        // so a configured one past the limits is kept until then
+        let (mut size, mut opacity) = (main.size, main.opacity);
+        if shown_pressed(vrapi::Action::SizeUp) {
+            size = (size * SIZE_STEP).min(MAX_SIZE).max(main.size);
+        } else if shown_pressed(vrapi::Action::SizeDown) {
+            size = (size / SIZE_STEP).max(MIN_SIZE).min(main.size);
+        }
+        if shown_pressed(vrapi::Action::OpacityUp) {
+            opacity = (opacity + OPACITY_STEP).min(1.0).max(main.opacity);
+        } else if shown_pressed(vrapi::Action::OpacityDown) {
+            opacity = (opacity - OPACITY_STEP).max(OPACITY_STEP).min(main.opacity);
         }
+        if (size, opacity) != (main.size, main.opacity) {
+            log::info!("{} overlay size {size}m, opacity {opacity}", main.name);
+            main.size = size;
+            main.opacity = opacity;
+            set_appearance(0, main, &mut *vrsys)?;
+        }
+        if shown_pressed(vrapi::Action::Snapshot) {
+            let dir = cfg
+                .snapshot_dir
+                .clone()
+                .unwrap_or_else(|| xdg.get_data_home().join("snapshots"));
+            take_snapshots(&views, &dir);
+        }
+    }
+    for view in views {
+        view.thread.join().unwrap()?;
     }
-    camera_thread.join().unwrap()?;
     Ok(())
 }
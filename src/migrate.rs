@@ -0,0 +1,157 @@
+//! Bring config files written for older versions up to date, and point out options that
+//! are not used.
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use toml::{Table, Value};
+
+/// Version of the config file format. Bump it when a migration is added to `MIGRATIONS`.
+pub(crate) const CONFIG_VERSION: u32 = 1;
+
+/// Migrations from each version to the next, starting from version 0, which is any file
+/// without a `version`.
+const MIGRATIONS: &[fn(&mut Table)] = &[v0_to_v1];
+
+/// Move `key` into the `to` table as `new_key`. The table is created if needed.
+fn move_key(table: &mut Table, key: &str, to: &str, new_key: &str) {
+    let Some(value) = table.remove(key) else {
+        return;
+    };
+    let to = table
+        .entry(to)
+        .or_insert_with(|| Value::Table(Table::new()));
+    if let Value::Table(to) = to {
+        to.entry(new_key).or_insert(value);
+    }
+}
+
+fn v0_to_v1(cfg: &mut Table) {
+    // The buttons are set up in the controller bindings now
+    cfg.remove("toggle_button");
+    // Each camera's device and display mode go with the rest of its options
+    move_key(cfg, "camera_device", "camera", "device");
+    move_key(cfg, "display_mode", "overlay", "display_mode");
+    if let Some(Value::Array(extras)) = cfg.get_mut("extra_cameras") {
+        for extra in extras.iter_mut().filter_map(Value::as_table_mut) {
+            move_key(extra, "device", "camera", "device");
+            move_key(extra, "display_mode", "overlay", "display_mode");
+        }
+    }
+}
+
+/// Migrate config file `path`, which has `text` in it, to the current version. Returns
+/// the version it had and what it becomes, `None` if the migrations don't change it.
+fn migrate(path: &Path, text: &str) -> Result<Option<(u32, String)>> {
+    let mut cfg: Table =
+        toml::from_str(text).with_context(|| anyhow!("invalid config file {}", path.display()))?;
+    let version = match cfg.get("version") {
+        None => 0,
+        Some(Value::Integer(version)) => u32::try_from(*version).unwrap_or(u32::MAX),
+        Some(_) => {
+            return Err(anyhow!(
+                "invalid config file {}: version must be a number",
+                path.display()
+            ))
+        }
+    };
+    if version > CONFIG_VERSION {
+        return Err(anyhow!(
+            "config file {} is version {version}, only versions up to {CONFIG_VERSION} are \
+             supported",
+            path.display()
+        ));
+    }
+    if version == CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    let original = cfg.clone();
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&mut cfg);
+    }
+    if cfg == original {
+        return Ok(None);
+    }
+    cfg.insert("version".to_owned(), Value::Integer(CONFIG_VERSION.into()));
+    Ok(Some((version, toml::to_string_pretty(&cfg)?)))
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path)
+        .with_context(|| anyhow!("cannot read config file {}", path.display()))
+}
+
+/// Read config file `path`, migrated to the current version. The file itself is left
+/// alone, see [`update_config_file`].
+pub(crate) fn read_config_file(path: &Path) -> Result<String> {
+    let text = read(path)?;
+    Ok(match migrate(path, &text)? {
+        Some((version, migrated)) => {
+            log::debug!("config file {} is version {version}", path.display());
+            migrated
+        }
+        None => text,
+    })
+}
+
+/// Rewrite config file `path` for the current version, if the migrations change it. The
+/// original is kept next to it with the version it had in its name. Files they don't
+/// change, like drop-ins that only set newer options, are left alone.
+pub(crate) fn update_config_file(path: &Path) -> Result<()> {
+    let text = read(path)?;
+    let Some((version, migrated)) = migrate(path, &text)? else {
+        return Ok(());
+    };
+    let backup = path.with_extension(format!("toml.v{version}"));
+    let result = std::fs::copy(path, &backup)
+        .and_then(|_| std::fs::write(path, &migrated))
+        .with_context(|| anyhow!("cannot update config file {}", path.display()));
+    match result {
+        Ok(()) => log::warn!(
+            "config file {} updated from version {version} to {CONFIG_VERSION}, the old one \
+             is kept as {}. comments in it are not carried over.",
+            path.display(),
+            backup.display()
+        ),
+        // Still usable, it will be migrated every time
+        Err(e) => log::warn!("{e:#}, it needs to be updated to version {CONFIG_VERSION}"),
+    }
+    Ok(())
+}
+
+/// Log the keys in `file` that `used` doesn't have, `used` being the config the file was
+/// deserialized into, serialized again. Anything deserialization ignored is missing from
+/// it, like a misspelled option, or one that doesn't apply to the `mode` chosen.
+pub(crate) fn warn_unknown_keys(path: &Path, file: &Value, used: &Value) {
+    fn walk(prefix: &str, file: &Value, used: &Value, unknown: &mut Vec<String>) {
+        match (file, used) {
+            (Value::Table(file), Value::Table(used)) => {
+                for (key, value) in file {
+                    let name = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{prefix}.{key}")
+                    };
+                    match used.get(key) {
+                        Some(used) => walk(&name, value, used, unknown),
+                        None => unknown.push(name),
+                    }
+                }
+            }
+            (Value::Array(file), Value::Array(used)) => {
+                for (index, (value, used)) in file.iter().zip(used).enumerate() {
+                    walk(&format!("{prefix}[{index}]"), value, used, unknown);
+                }
+            }
+            _ => (),
+        }
+    }
+    let mut unknown = Vec::new();
+    walk("", file, used, &mut unknown);
+    for key in unknown {
+        log::warn!(
+            "unknown option {key} in config file {}, it is ignored",
+            path.display()
+        );
+    }
+}
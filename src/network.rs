@@ -0,0 +1,562 @@
+//! Frame sources receiving frames over the network, for cameras attached to other
+//! machines.
+//!
+//! Two kinds of streams are supported:
+//!
+//! - MJPEG over HTTP, the `multipart/x-mixed-replace` stream served by most IP cameras
+//!   and phone camera apps.
+//! - Our own framing, over TCP or UDP. Each frame is sent as a [`FrameHeader`] followed
+//!   by the frame data, in any format the pipeline takes. Over UDP a frame can be split
+//!   across datagrams, each with its own header giving where its data goes in the frame.
+//!
+//! The format of a stream is whatever its first frame has, it can't be changed by us.
+//! `examples/frame_server.rs` serves a test pattern in all of these.
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpStream, UdpSocket},
+    ops::Range,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::camera::{Frame, FrameFormat, FrameSource, Stalled};
+
+/// How long we wait for the first frame when opening a stream.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Largest frame we accept, so a broken header can't make us allocate arbitrary amounts.
+const MAX_FRAME_SIZE: u32 = 64 << 20;
+const MAGIC: [u8; 4] = *b"ICPF";
+
+/// Header of a frame in our framing, all fields are little endian.
+///
+/// | offset | size | field       |
+/// |--------|------|-------------|
+/// | 0      | 4    | `b"ICPF"`   |
+/// | 4      | 4    | `fourcc`    |
+/// | 8      | 4    | `width`     |
+/// | 12     | 4    | `height`    |
+/// | 16     | 4    | `sequence`  |
+/// | 20     | 8    | `timestamp` |
+/// | 28     | 4    | `length`    |
+/// | 32     | 4    | `offset`    |
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FrameHeader {
+    pub fourcc: v4l::FourCC,
+    pub width: u32,
+    pub height: u32,
+    pub sequence: u32,
+    /// when the frame was captured, in microseconds of the sender's monotonic clock
+    pub timestamp: u64,
+    /// size of the whole frame
+    pub length: u32,
+    /// where the data following this header goes in the frame, always 0 over TCP
+    pub offset: u32,
+}
+
+impl FrameHeader {
+    pub(crate) const SIZE: usize = 36;
+    fn parse(bytes: &[u8; Self::SIZE]) -> Result<Self> {
+        if bytes[..4] != MAGIC {
+            return Err(anyhow!("bad frame header"));
+        }
+        let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..][..4].try_into().unwrap());
+        let header = Self {
+            fourcc: v4l::FourCC::new(bytes[4..8].try_into().unwrap()),
+            width: u32_at(8),
+            height: u32_at(12),
+            sequence: u32_at(16),
+            timestamp: u64::from_le_bytes(bytes[20..28].try_into().unwrap()),
+            length: u32_at(28),
+            offset: u32_at(32),
+        };
+        if header.length > MAX_FRAME_SIZE {
+            return Err(anyhow!("frame too large: {} bytes", header.length));
+        }
+        Ok(header)
+    }
+    fn format(&self) -> FrameFormat {
+        FrameFormat {
+            width: self.width,
+            height: self.height,
+            fourcc: self.fourcc,
+            fps: 0,
+        }
+    }
+    fn frame<'a>(&self, data: &'a [u8]) -> Frame<'a> {
+        Frame {
+            data,
+            timestamp: Duration::from_micros(self.timestamp),
+            sequence: self.sequence,
+            monotonic: false,
+            latency: None,
+            error: false,
+        }
+    }
+}
+
+/// Turn socket timeouts into [`Stalled`].
+fn map_io_error(e: std::io::Error, timeout: Option<Duration>) -> anyhow::Error {
+    match e.kind() {
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+            Stalled(timeout.unwrap_or(PROBE_TIMEOUT)).into()
+        }
+        _ => e.into(),
+    }
+}
+
+/// Receive frames in our framing over TCP.
+pub(crate) struct TcpSource {
+    address: String,
+    stream: Option<BufReader<TcpStream>>,
+    format: FrameFormat,
+    header: FrameHeader,
+    buffer: Vec<u8>,
+    /// the frame in `buffer` was received when opening the stream, and not returned yet
+    pending: bool,
+    timeout: Option<Duration>,
+}
+
+impl TcpSource {
+    /// Connect to `address`, and wait for the first frame to learn the format.
+    pub(crate) fn open(address: &str) -> Result<Self> {
+        let mut stream = Self::connect(address, Some(PROBE_TIMEOUT))?;
+        let mut buffer = Vec::new();
+        let header = Self::read_frame(&mut stream, &mut buffer, None)?;
+        log::info!("receiving {} from {address}", header.format());
+        Ok(Self {
+            address: address.to_owned(),
+            stream: Some(stream),
+            format: header.format(),
+            header,
+            buffer,
+            pending: true,
+            timeout: None,
+        })
+    }
+    fn connect(address: &str, timeout: Option<Duration>) -> Result<BufReader<TcpStream>> {
+        let stream =
+            TcpStream::connect(address).with_context(|| anyhow!("cannot connect to {address}"))?;
+        stream.set_read_timeout(timeout)?;
+        stream.set_nodelay(true)?;
+        Ok(BufReader::new(stream))
+    }
+    fn read_frame(
+        stream: &mut BufReader<TcpStream>,
+        buffer: &mut Vec<u8>,
+        timeout: Option<Duration>,
+    ) -> Result<FrameHeader> {
+        let mut header = [0; FrameHeader::SIZE];
+        stream
+            .read_exact(&mut header)
+            .map_err(|e| map_io_error(e, timeout))?;
+        let header = FrameHeader::parse(&header)?;
+        if header.offset != 0 {
+            return Err(anyhow!("frames can't be split over TCP"));
+        }
+        buffer.resize(header.length as usize, 0);
+        stream
+            .read_exact(buffer)
+            .map_err(|e| map_io_error(e, timeout))?;
+        Ok(header)
+    }
+}
+
+impl FrameSource for TcpSource {
+    fn supported_formats(&self) -> Result<Vec<FrameFormat>> {
+        Ok(vec![self.format])
+    }
+    fn negotiate_format(&mut self, _requested: &FrameFormat) -> Result<FrameFormat> {
+        // The sender decides the format
+        Ok(self.format)
+    }
+    fn start(&mut self) -> Result<()> {
+        match &self.stream {
+            Some(stream) => stream.get_ref().set_read_timeout(self.timeout)?,
+            None => self.stream = Some(Self::connect(&self.address, self.timeout)?),
+        }
+        Ok(())
+    }
+    fn next_frame(&mut self) -> Result<Frame<'_>> {
+        let stream = self
+            .stream
+            .as_mut()
+            .with_context(|| anyhow!("network stream not started"))?;
+        if !self.pending {
+            let header = Self::read_frame(stream, &mut self.buffer, self.timeout)?;
+            if header.format() != self.format {
+                return Err(anyhow!("network stream changed to {}", header.format()));
+            }
+            self.header = header;
+        }
+        self.pending = false;
+        Ok(self.header.frame(&self.buffer))
+    }
+    fn stop(&mut self) -> Result<()> {
+        // Reconnect when started again, in case the connection is what's stuck.
+        self.stream = None;
+        self.pending = false;
+        Ok(())
+    }
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+/// Receive frames in our framing over UDP. Datagrams can arrive out of order, but a
+/// datagram of a different frame than the one being put together means the rest of
+/// that frame is lost.
+pub(crate) struct UdpSource {
+    address: String,
+    socket: Option<UdpSocket>,
+    format: FrameFormat,
+    header: FrameHeader,
+    buffer: Vec<u8>,
+    /// the frame in `buffer` was received when opening the stream, and not returned yet
+    pending: bool,
+    timeout: Option<Duration>,
+    /// frame being put together, and the byte ranges of it we have, sorted and disjoint
+    partial: Option<(FrameHeader, Vec<Range<usize>>)>,
+    assembly: Vec<u8>,
+    datagram: Vec<u8>,
+}
+
+impl UdpSource {
+    /// Receive on the local `address`, and wait for the first frame to learn the format.
+    pub(crate) fn open(address: &str) -> Result<Self> {
+        let mut source = Self {
+            address: address.to_owned(),
+            socket: Some(Self::bind(address, Some(PROBE_TIMEOUT))?),
+            format: FrameFormat {
+                width: 0,
+                height: 0,
+                fourcc: v4l::FourCC::new(&[0; 4]),
+                fps: 0,
+            },
+            header: FrameHeader {
+                fourcc: v4l::FourCC::new(&[0; 4]),
+                width: 0,
+                height: 0,
+                sequence: 0,
+                timestamp: 0,
+                length: 0,
+                offset: 0,
+            },
+            buffer: Vec::new(),
+            pending: true,
+            timeout: None,
+            partial: None,
+            assembly: Vec::new(),
+            datagram: vec![0; 65536],
+        };
+        source.receive_frame()?;
+        source.format = source.header.format();
+        log::info!("receiving {} on {address}", source.format);
+        Ok(source)
+    }
+    fn bind(address: &str, timeout: Option<Duration>) -> Result<UdpSocket> {
+        let socket =
+            UdpSocket::bind(address).with_context(|| anyhow!("cannot receive on {address}"))?;
+        socket.set_read_timeout(timeout)?;
+        Ok(socket)
+    }
+    /// Receive datagrams until a whole frame is in `buffer`.
+    fn receive_frame(&mut self) -> Result<()> {
+        let socket = self
+            .socket
+            .as_ref()
+            .with_context(|| anyhow!("network stream not started"))?;
+        loop {
+            let len = socket
+                .recv(&mut self.datagram)
+                .map_err(|e| map_io_error(e, self.timeout))?;
+            let Some(header) = self.datagram[..len].first_chunk::<{ FrameHeader::SIZE }>() else {
+                log::debug!("datagram too short: {len} bytes");
+                continue;
+            };
+            let header = match FrameHeader::parse(header) {
+                Ok(header) => header,
+                Err(e) => {
+                    log::debug!("bad datagram: {e:#}");
+                    continue;
+                }
+            };
+            let data = &self.datagram[FrameHeader::SIZE..len];
+            let (start, end) = (header.offset as usize, header.offset as usize + data.len());
+            if end > header.length as usize {
+                log::debug!("datagram past the end of frame {}", header.sequence);
+                continue;
+            }
+            if let Some((partial, received)) = &self.partial {
+                if partial.sequence != header.sequence || partial.length != header.length {
+                    log::trace!(
+                        "frame {} incomplete, got {} of {} bytes",
+                        partial.sequence,
+                        received.iter().map(|r| r.len()).sum::<usize>(),
+                        partial.length
+                    );
+                    self.partial = None;
+                }
+            }
+            if self.partial.is_none() {
+                self.assembly.resize(header.length as usize, 0);
+            }
+            let (partial, received) = self.partial.get_or_insert((header, Vec::new()));
+            self.assembly[start..end].copy_from_slice(data);
+            // Duplicated datagrams mustn't count twice, so keep track of which bytes we have
+            add_range(received, start..end);
+            if received.iter().map(|r| r.len()).sum::<usize>() >= partial.length as usize {
+                self.header = *partial;
+                self.partial = None;
+                std::mem::swap(&mut self.buffer, &mut self.assembly);
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Add `range` to the sorted, disjoint `ranges`, merging it with the ones it touches.
+fn add_range(ranges: &mut Vec<Range<usize>>, mut range: Range<usize>) {
+    let first = ranges.partition_point(|r| r.end < range.start);
+    let last = ranges.partition_point(|r| r.start <= range.end);
+    if first < last {
+        range.start = range.start.min(ranges[first].start);
+        range.end = range.end.max(ranges[last - 1].end);
+    }
+    ranges.splice(first..last, [range]);
+}
+
+impl FrameSource for UdpSource {
+    fn supported_formats(&self) -> Result<Vec<FrameFormat>> {
+        Ok(vec![self.format])
+    }
+    fn negotiate_format(&mut self, _requested: &FrameFormat) -> Result<FrameFormat> {
+        // The sender decides the format
+        Ok(self.format)
+    }
+    fn start(&mut self) -> Result<()> {
+        match &self.socket {
+            Some(socket) => socket.set_read_timeout(self.timeout)?,
+            None => self.socket = Some(Self::bind(&self.address, self.timeout)?),
+        }
+        Ok(())
+    }
+    fn next_frame(&mut self) -> Result<Frame<'_>> {
+        if !self.pending {
+            self.receive_frame()?;
+            if self.header.format() != self.format {
+                return Err(anyhow!(
+                    "network stream changed to {}",
+                    self.header.format()
+                ));
+            }
+        }
+        self.pending = false;
+        Ok(self.header.frame(&self.buffer))
+    }
+    fn stop(&mut self) -> Result<()> {
+        self.socket = None;
+        self.partial = None;
+        self.pending = false;
+        Ok(())
+    }
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+/// Receive a MJPEG stream over HTTP. Only plain `http://` URLs are supported.
+pub(crate) struct HttpSource {
+    url: String,
+    /// the connection, and the boundary between frames
+    stream: Option<(BufReader<TcpStream>, String)>,
+    format: FrameFormat,
+    buffer: Vec<u8>,
+    /// when the frame in `buffer` arrived
+    timestamp: Duration,
+    sequence: u32,
+    /// the frame in `buffer` was received when opening the stream, and not returned yet
+    pending: bool,
+    timeout: Option<Duration>,
+}
+
+impl HttpSource {
+    /// Request the stream at `url`, and wait for the first frame to learn its size.
+    pub(crate) fn open(url: &str) -> Result<Self> {
+        let (mut stream, boundary) = Self::connect(url, Some(PROBE_TIMEOUT))?;
+        let mut buffer = Vec::new();
+        Self::read_part(&mut stream, &boundary, &mut buffer, None)?;
+        let image = image::load_from_memory_with_format(&buffer, image::ImageFormat::Jpeg)
+            .context("first frame of the stream is not a JPEG image")?;
+        let format = FrameFormat {
+            width: image.width(),
+            height: image.height(),
+            fourcc: crate::config::PixelFormat::MJPG.fourcc(),
+            fps: 0,
+        };
+        log::info!("receiving {format} from {url}");
+        Ok(Self {
+            url: url.to_owned(),
+            stream: Some((stream, boundary)),
+            format,
+            buffer,
+            timestamp: crate::clock::monotonic_now(),
+            sequence: 0,
+            pending: true,
+            timeout: None,
+        })
+    }
+    fn connect(url: &str, timeout: Option<Duration>) -> Result<(BufReader<TcpStream>, String)> {
+        let rest = url
+            .strip_prefix("http://")
+            .with_context(|| anyhow!("only http:// URLs are supported, got {url}"))?;
+        let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let address = if host.contains(':') {
+            host.to_owned()
+        } else {
+            format!("{host}:80")
+        };
+        let mut stream =
+            TcpStream::connect(&address).with_context(|| anyhow!("cannot connect to {host}"))?;
+        stream.set_read_timeout(timeout)?;
+        write!(
+            stream,
+            "GET /{path} HTTP/1.0\r\nHost: {host}\r\nAccept: multipart/x-mixed-replace\r\n\r\n"
+        )?;
+        let mut stream = BufReader::new(stream);
+        let status = Self::read_line(&mut stream, timeout)?;
+        if status.split_whitespace().nth(1) != Some("200") {
+            return Err(anyhow!("{url}: {status}"));
+        }
+        let content_type = Self::read_headers(&mut stream, timeout)?
+            .into_iter()
+            .find_map(|(name, value)| (name == "content-type").then_some(value))
+            .unwrap_or_default();
+        let boundary = content_type
+            .split(';')
+            .find_map(|param| param.trim().strip_prefix("boundary="))
+            .with_context(|| anyhow!("{url} is not a MJPEG stream: {content_type}"))?;
+        // Some servers put the leading dashes of the delimiter in the boundary as well
+        let boundary = boundary
+            .trim_matches('"')
+            .trim_start_matches('-')
+            .to_owned();
+        Ok((stream, boundary))
+    }
+    fn read_line(stream: &mut BufReader<TcpStream>, timeout: Option<Duration>) -> Result<String> {
+        let mut line = String::new();
+        if stream
+            .read_line(&mut line)
+            .map_err(|e| map_io_error(e, timeout))?
+            == 0
+        {
+            return Err(anyhow!("connection closed"));
+        }
+        Ok(line.trim_end().to_owned())
+    }
+    /// Read headers up to the empty line ending them. Names are lowercased.
+    fn read_headers(
+        stream: &mut BufReader<TcpStream>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<(String, String)>> {
+        let mut headers = Vec::new();
+        loop {
+            let line = Self::read_line(stream, timeout)?;
+            if line.is_empty() {
+                return Ok(headers);
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_ascii_lowercase(), value.trim().to_owned()));
+            }
+        }
+    }
+    /// Read the next part of the multipart stream into `buffer`.
+    fn read_part(
+        stream: &mut BufReader<TcpStream>,
+        boundary: &str,
+        buffer: &mut Vec<u8>,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        while Self::read_line(stream, timeout)?.trim_start_matches('-') != boundary {}
+        let length = Self::read_headers(stream, timeout)?
+            .into_iter()
+            .find_map(|(name, value)| (name == "content-length").then_some(value))
+            .map(|length| length.parse::<u32>())
+            .transpose()
+            .context("bad Content-Length")?;
+        buffer.clear();
+        match length {
+            Some(length) if length > MAX_FRAME_SIZE => {
+                return Err(anyhow!("frame too large: {length} bytes"))
+            }
+            Some(length) => {
+                buffer.resize(length as usize, 0);
+                stream
+                    .read_exact(buffer)
+                    .map_err(|e| map_io_error(e, timeout))?;
+            }
+            // Without a length, the frame ends with the JPEG EOI marker
+            None => {
+                while !buffer.ends_with(&[0xff, 0xd9]) {
+                    if stream
+                        .read_until(0xd9, buffer)
+                        .map_err(|e| map_io_error(e, timeout))?
+                        == 0
+                    {
+                        return Err(anyhow!("connection closed"));
+                    }
+                    if buffer.len() > MAX_FRAME_SIZE as usize {
+                        return Err(anyhow!("frame too large"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FrameSource for HttpSource {
+    fn supported_formats(&self) -> Result<Vec<FrameFormat>> {
+        Ok(vec![self.format])
+    }
+    fn negotiate_format(&mut self, _requested: &FrameFormat) -> Result<FrameFormat> {
+        // The sender decides the format
+        Ok(self.format)
+    }
+    fn start(&mut self) -> Result<()> {
+        match &self.stream {
+            Some((stream, _)) => stream.get_ref().set_read_timeout(self.timeout)?,
+            None => self.stream = Some(Self::connect(&self.url, self.timeout)?),
+        }
+        Ok(())
+    }
+    fn next_frame(&mut self) -> Result<Frame<'_>> {
+        let (stream, boundary) = self
+            .stream
+            .as_mut()
+            .with_context(|| anyhow!("network stream not started"))?;
+        if !self.pending {
+            Self::read_part(stream, boundary, &mut self.buffer, self.timeout)?;
+            // The stream doesn't tell us when the frame was captured
+            self.timestamp = crate::clock::monotonic_now();
+            self.sequence = self.sequence.wrapping_add(1);
+        }
+        self.pending = false;
+        Ok(Frame {
+            data: &self.buffer,
+            timestamp: self.timestamp,
+            sequence: self.sequence,
+            monotonic: true,
+            latency: None,
+            error: false,
+        })
+    }
+    fn stop(&mut self) -> Result<()> {
+        self.stream = None;
+        self.pending = false;
+        Ok(())
+    }
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
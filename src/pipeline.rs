@@ -1,12 +1,16 @@
 use std::sync::Arc;
 
-use crate::utils::{Array, DeviceExt as _};
-use anyhow::Result;
+use crate::{
+    config::CameraLayout,
+    utils::{Array, DeviceExt as _},
+};
+use anyhow::{anyhow, Result};
 use vulkano::{
     buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer},
     command_buffer::{
         allocator::CommandBufferAllocator, CommandBufferBeginInfo, CommandBufferLevel,
-        CommandBufferUsage, CopyBufferToImageInfo, RecordingCommandBuffer,
+        CommandBufferUsage, CopyBufferToImageInfo, CopyImageInfo, ImageCopy,
+        RecordingCommandBuffer,
     },
     descriptor_set::allocator::DescriptorSetAllocator,
     device::{Device, DeviceOwned},
@@ -24,8 +28,13 @@ pub(crate) struct Pipeline {
     render_doc: Option<renderdoc::RenderDoc<renderdoc::V100>>,
     cpu_image_buffer: Arc<Buffer>,
     yuv_texture: Arc<VkImage>,
+    /// camera image before it's rearranged into side-by-side, if it's top-bottom
+    raw_texture: Option<Arc<VkImage>>,
     textures: [Arc<VkImage>; 2],
-    camera_config: Option<crate::vrapi::StereoCamera>,
+    camera_config: Option<crate::vrapi::CameraCalibration>,
+    layout: CameraLayout,
+    /// size of the camera image
+    extent: [u32; 2],
 }
 
 impl std::fmt::Debug for Pipeline {
@@ -36,17 +45,21 @@ impl std::fmt::Debug for Pipeline {
             .field("capture", &self.capture)
             .field("render_doc", &self.render_doc)
             .field("yuv_texture", &self.yuv_texture.handle().as_raw())
+            .field(
+                "raw_texture",
+                &self.raw_texture.as_ref().map(|t| t.handle().as_raw()),
+            )
             .field(
                 "textures",
                 &self.textures.each_ref().map(|t| t.handle().as_raw()),
             )
             .field("camera_config", &self.camera_config)
+            .field("layout", &self.layout)
+            .field("extent", &self.extent)
             .finish_non_exhaustive()
     }
 }
 
-use crate::CAMERA_SIZE;
-
 enum EitherGpuFuture<L, R> {
     Left(L),
     Right(R),
@@ -167,13 +180,20 @@ unsafe impl<L: GpuFuture, R: GpuFuture> GpuFuture for EitherGpuFuture<L, R> {
 }
 
 impl Pipeline {
+    /// Upload `img` of size `extent` into the top left corner of `output`.
     pub(crate) fn submit_cpu_image(
         &self,
         img: &[u8],
+        extent: [u32; 2],
         cmdbuf_allocator: Arc<dyn CommandBufferAllocator>,
         queue: &Arc<vulkano::device::Queue>,
         output: Arc<VkImage>,
     ) -> Result<impl GpuFuture> {
+        // 4 bytes per texel, YUYV is packed two pixels to a texel
+        let expected = extent[0] as usize * extent[1] as usize * 4;
+        if img.len() != expected {
+            return Err(anyhow!("image is {} bytes, expected {expected}", img.len()));
+        }
         let buffer = Subbuffer::new(self.cpu_image_buffer.clone()).slice(0..img.len() as u64);
         buffer.write()?.copy_from_slice(img);
         let mut cmdbuf = RecordingCommandBuffer::new(
@@ -185,23 +205,109 @@ impl Pipeline {
                 ..Default::default()
             },
         )?;
-        cmdbuf.copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(buffer, output))?;
+        let mut copy = CopyBufferToImageInfo::buffer_image(buffer, output);
+        copy.regions[0].image_extent = [extent[0], extent[1], 1];
+        cmdbuf.copy_buffer_to_image(copy)?;
         Ok(cmdbuf.end()?.execute(queue.clone())?)
     }
 
+    /// Size of the overlay texture, which has a view for each eye side by side, for a camera
+    /// image of size `extent`.
+    pub(crate) fn stereo_extent(layout: CameraLayout, extent: [u32; 2]) -> [u32; 2] {
+        let [width, height] = extent;
+        match layout {
+            CameraLayout::SideBySide => extent,
+            CameraLayout::TopBottom => [width * 2, height / 2],
+            CameraLayout::Mono => [width * 2, height],
+        }
+    }
+
+    /// Size of the image the pipeline outputs for a camera image of size `extent`. Same as
+    /// [`Self::stereo_extent`], except a mono image is kept as a single view.
+    pub(crate) fn output_extent(layout: CameraLayout, extent: [u32; 2]) -> [u32; 2] {
+        match layout {
+            CameraLayout::Mono => extent,
+            _ => Self::stereo_extent(layout, extent),
+        }
+    }
+
+    /// Copy the views in `raw_texture` side by side into `output`.
+    fn arrange(
+        &self,
+        after: impl GpuFuture,
+        cmdbuf_allocator: Arc<dyn CommandBufferAllocator>,
+        queue: &Arc<vulkano::device::Queue>,
+        raw_texture: Arc<VkImage>,
+        output: Arc<VkImage>,
+    ) -> Result<impl GpuFuture> {
+        let [width, height] = self.extent;
+        // (source offset, view size) of each view
+        let views = match self.layout {
+            CameraLayout::SideBySide => vec![
+                ([0, 0], [width / 2, height]),
+                ([width / 2, 0], [width / 2, height]),
+            ],
+            CameraLayout::TopBottom => vec![
+                ([0, 0], [width, height / 2]),
+                ([0, height / 2], [width, height / 2]),
+            ],
+            CameraLayout::Mono => vec![([0, 0], [width, height])],
+        };
+        let mut dst_x = 0;
+        let regions = views
+            .into_iter()
+            .map(|([src_x, src_y], [view_width, view_height])| {
+                let region = ImageCopy {
+                    src_subresource: raw_texture.subresource_layers(),
+                    src_offset: [src_x, src_y, 0],
+                    dst_subresource: output.subresource_layers(),
+                    dst_offset: [dst_x, 0, 0],
+                    extent: [view_width, view_height, 1],
+                    ..Default::default()
+                };
+                dst_x += view_width;
+                region
+            })
+            .collect();
+        let mut cmdbuf = RecordingCommandBuffer::new(
+            cmdbuf_allocator,
+            queue.queue_family_index(),
+            CommandBufferLevel::Primary,
+            CommandBufferBeginInfo {
+                usage: CommandBufferUsage::OneTimeSubmit,
+                ..Default::default()
+            },
+        )?;
+        cmdbuf.copy_image(CopyImageInfo {
+            regions,
+            ..CopyImageInfo::images(raw_texture, output)
+        })?;
+        Ok(after.then_execute(queue.clone(), cmdbuf.end()?)?)
+    }
+
     /// Create post-processing stages
     ///
     /// Camera data -> upload -> internal texture
     /// internal texture -> YUYV conversion -> textures[0]
     /// textures[0] -> Lens correction -> textures[1]
     /// textures[1] -> projection -> Final output
+    ///
+    /// If the camera image is top-bottom, it's converted into `raw_texture` first,
+    /// then rearranged into textures[0]. A mono image stays a single view throughout, and is
+    /// written into the top left corner of the output, see [`Self::output_extent`].
+    ///
+    /// `extent` is the size of the camera image, in pixels.
     pub(crate) fn new(
         device: Arc<Device>,
         allocator: Arc<dyn MemoryAllocator>,
         descriptor_set_allocator: Arc<dyn DescriptorSetAllocator>,
         source_is_yuv: bool,
-        camera_config: Option<crate::vrapi::StereoCamera>,
+        camera_config: Option<crate::vrapi::CameraCalibration>,
+        extent: [u32; 2],
+        layout: CameraLayout,
     ) -> Result<Self> {
+        let [width, height] = extent;
+        let [output_width, output_height] = Self::output_extent(layout, extent);
         let render_doc = renderdoc::RenderDoc::new().ok();
         if render_doc.is_some() {
             log::info!("RenderDoc loaded");
@@ -209,7 +315,8 @@ impl Pipeline {
         // Allocate intermediate textures
         let yuv_texture = device.clone().new_image(
             ImageCreateInfo {
-                extent: [CAMERA_SIZE, CAMERA_SIZE, 1],
+                // Two pixels are packed into one texel in YUYV
+                extent: [width / 2, height, 1],
                 format: Format::R8G8B8A8_UNORM,
                 usage: ImageUsage::TRANSFER_DST
                     | ImageUsage::TRANSFER_SRC
@@ -224,9 +331,12 @@ impl Pipeline {
             .map(|id| {
                 let tex = device.clone().new_image(
                     ImageCreateInfo {
-                        extent: [CAMERA_SIZE * 2, CAMERA_SIZE, 1],
+                        extent: [output_width, output_height, 1],
                         format: Format::R8G8B8A8_UNORM,
-                        usage: ImageUsage::SAMPLED | ImageUsage::COLOR_ATTACHMENT,
+                        // TRANSFER_DST because RGB sources are uploaded directly
+                        usage: ImageUsage::SAMPLED
+                            | ImageUsage::COLOR_ATTACHMENT
+                            | ImageUsage::TRANSFER_DST,
                         ..Default::default()
                     },
                     MemoryTypeFilter::PREFER_DEVICE,
@@ -236,6 +346,23 @@ impl Pipeline {
             })
             .collect::<Result<Array<_, 2>, _>>()?
             .into_inner();
+        let raw_texture = (layout == CameraLayout::TopBottom)
+            .then(|| {
+                let tex = device.clone().new_image(
+                    ImageCreateInfo {
+                        extent: [width, height, 1],
+                        format: Format::R8G8B8A8_UNORM,
+                        usage: ImageUsage::COLOR_ATTACHMENT
+                            | ImageUsage::TRANSFER_DST
+                            | ImageUsage::TRANSFER_SRC,
+                        ..Default::default()
+                    },
+                    MemoryTypeFilter::PREFER_DEVICE,
+                )?;
+                device.set_debug_utils_object_name(&tex, Some("raw_texture"))?;
+                anyhow::Ok(tex)
+            })
+            .transpose()?;
         // if source is YUV: upload -> yuv_texture -> converter -> output_a
         // if source is RGB: upload -> output_a
         let converter = source_is_yuv
@@ -243,8 +370,8 @@ impl Pipeline {
                 crate::yuv::GpuYuyvConverter::new(
                     device.clone(),
                     descriptor_set_allocator.clone(),
-                    CAMERA_SIZE * 2,
-                    CAMERA_SIZE,
+                    width,
+                    height,
                     &yuv_texture,
                 )
             })
@@ -265,9 +392,9 @@ impl Pipeline {
         let cpu_buffer = device.clone().new_buffer(
             BufferCreateInfo {
                 usage: BufferUsage::TRANSFER_SRC,
-                // This should be more than enough. Camera sources are YUV subsampled,
-                // so it won't be 4 bytes per pixel.
-                size: CAMERA_SIZE as u64 * CAMERA_SIZE as u64 * 2 * 4,
+                // Large enough for a decoded RGBA frame, YUV sources are subsampled so
+                // they need less.
+                size: width as u64 * height as u64 * 4,
                 ..Default::default()
             },
             MemoryTypeFilter::HOST_SEQUENTIAL_WRITE | MemoryTypeFilter::PREFER_DEVICE,
@@ -285,8 +412,11 @@ impl Pipeline {
             render_doc,
             textures,
             yuv_texture,
+            raw_texture,
             camera_config,
             cpu_image_buffer: cpu_buffer,
+            layout,
+            extent,
         })
     }
     pub fn fov(&self) -> [[f32; 2]; 2] {
@@ -322,9 +452,12 @@ impl Pipeline {
         } else {
             output.clone()
         };
+        let converted = self.raw_texture.clone().unwrap_or_else(|| texture.clone());
         let future = if let Some(converter) = &self.yuv {
+            let [yuv_width, yuv_height, _] = self.yuv_texture.extent();
             let future = self.submit_cpu_image(
                 input,
+                [yuv_width, yuv_height],
                 cmdbuf_allocator.clone(),
                 queue,
                 self.yuv_texture.clone(),
@@ -334,12 +467,28 @@ impl Pipeline {
                 cmdbuf_allocator.clone(),
                 future,
                 queue,
-                texture.clone(),
+                converted.clone(),
             )?;
             EitherGpuFuture::Left(future)
         } else {
-            let future =
-                self.submit_cpu_image(input, cmdbuf_allocator.clone(), queue, texture.clone())?;
+            let future = self.submit_cpu_image(
+                input,
+                self.extent,
+                cmdbuf_allocator.clone(),
+                queue,
+                converted.clone(),
+            )?;
+            EitherGpuFuture::Right(future)
+        };
+        let future = if let Some(raw_texture) = &self.raw_texture {
+            EitherGpuFuture::Left(self.arrange(
+                future,
+                cmdbuf_allocator.clone(),
+                queue,
+                raw_texture.clone(),
+                texture,
+            )?)
+        } else {
             EitherGpuFuture::Right(future)
         };
         future.flush()?;
@@ -0,0 +1,136 @@
+//! Remember where `Sticky` overlays were placed, so they come back in the same place
+//! after a restart.
+//!
+//! Placements are kept per tracking universe, the same spot means something else once
+//! the play area is set up again.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use nalgebra::Affine3;
+use serde::{Deserialize, Serialize};
+use xdg::BaseDirectories;
+
+use crate::config::{deserialize_transform, serialize_transform, PositionMode};
+
+const STATE_FILE: &str = "placements.toml";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct Placement {
+    #[serde(
+        serialize_with = "serialize_transform",
+        deserialize_with = "deserialize_transform"
+    )]
+    transform: Affine3<f32>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct State {
+    /// placement of each overlay, by overlay name, in each tracking universe
+    #[serde(default)]
+    universes: BTreeMap<String, BTreeMap<String, Placement>>,
+}
+
+fn read_state(path: &Path) -> Result<State> {
+    let state =
+        std::fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+    toml::from_str(&state).with_context(|| format!("invalid state file {}", path.display()))
+}
+
+pub(crate) struct Placements {
+    path: Option<PathBuf>,
+    state: State,
+    /// placements changed since the state file was written
+    dirty: bool,
+    /// tracking universe overlays are placed in, nothing is restored or remembered
+    /// without one
+    universe: Option<String>,
+}
+
+impl Placements {
+    /// Load the placements saved last time. Problems with the state file are logged, and
+    /// start us with no placements.
+    pub(crate) fn load(xdg: &BaseDirectories) -> Self {
+        let path = xdg
+            .place_state_file(STATE_FILE)
+            .map_err(|e| log::warn!("cannot save overlay placements: {e}"))
+            .ok();
+        let state = match &path {
+            Some(path) if path.exists() => read_state(path).unwrap_or_else(|e| {
+                log::warn!("cannot load overlay placements: {e:#}");
+                State::default()
+            }),
+            _ => State::default(),
+        };
+        Self {
+            path,
+            state,
+            dirty: false,
+            universe: None,
+        }
+    }
+    /// Set the tracking universe overlays are placed in from now on.
+    pub(crate) fn set_universe(&mut self, universe: Option<String>) {
+        log::debug!("tracking universe: {universe:?}");
+        self.universe = universe;
+    }
+    /// Put a `Sticky` overlay that hasn't been placed where it was placed last time in
+    /// the current tracking universe, if it was. Other positions are returned as is.
+    pub(crate) fn restore(&self, overlay: &str, position: PositionMode) -> PositionMode {
+        let Some(universe) = &self.universe else {
+            return position;
+        };
+        match position {
+            PositionMode::Sticky {
+                distance,
+                transform: None,
+            } => {
+                let placement = self
+                    .state
+                    .universes
+                    .get(universe)
+                    .and_then(|overlays| overlays.get(overlay));
+                if placement.is_some() {
+                    log::debug!("restoring where the {overlay} overlay was placed");
+                }
+                PositionMode::Sticky {
+                    distance,
+                    transform: placement.map(|placement| placement.transform),
+                }
+            }
+            _ => position,
+        }
+    }
+    /// Remember `overlay` is placed at `transform` in the current tracking universe.
+    /// Written to the state file by `save`.
+    pub(crate) fn set(&mut self, overlay: &str, transform: Affine3<f32>) {
+        let Some(universe) = &self.universe else {
+            return;
+        };
+        self.state
+            .universes
+            .entry(universe.clone())
+            .or_default()
+            .insert(overlay.to_owned(), Placement { transform });
+        self.dirty = true;
+    }
+    /// Write the placements to the state file, if they changed.
+    pub(crate) fn save(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+        self.dirty = false;
+        // Write a new file and rename it over the old one, so a crash doesn't lose the
+        // placements
+        let tmp = path.with_extension("toml.tmp");
+        std::fs::write(&tmp, toml::to_string(&self.state)?)
+            .with_context(|| format!("cannot write {}", tmp.display()))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("cannot write {}", path.display()))?;
+        Ok(())
+    }
+}
@@ -70,7 +70,7 @@ pub struct ProjectionParameters {
     pub overlay_width: f32,
     /// MVP matrices for the left and right eye, respectively.
     pub mvps: [Matrix4<f32>; 2],
-    pub camera_calib: Option<crate::vrapi::StereoCamera>,
+    pub camera_calib: Option<crate::vrapi::CameraCalibration>,
     pub mode: ProjectionMode,
 }
 
@@ -88,7 +88,8 @@ impl std::fmt::Debug for Uniforms {
 
 #[derive(Debug)]
 pub struct Projection {
-    extent: [u32; 2],
+    /// the source is a single view seen by both eyes, instead of two views side by side
+    mono: bool,
     pipeline: Arc<GraphicsPipeline>,
     render_pass: Arc<RenderPass>,
     // [0: left, 1: right]
@@ -96,6 +97,7 @@ pub struct Projection {
     saved_parameters: ProjectionParameters,
     mode_ipd_changed: bool,
     mvps_changed: bool,
+    overlay_width_changed: bool,
     desc_sets: [Arc<DescriptorSet>; 2],
 }
 use crate::{config::ProjectionMode, utils::Array};
@@ -131,8 +133,8 @@ fn format_matrix<
 
 #[derive(thiserror::Error, Debug)]
 pub enum ProjectorError {
-    #[error("input image is not square {0}x{1}")]
-    NotSquare(u32, u32),
+    #[error("input image {0}x{1} is not two views side by side")]
+    NotSideBySide(u32, u32),
     #[error("vulkan error {0}")]
     Vulkan(#[from] Validated<VulkanError>),
     #[error("{0}")]
@@ -168,7 +170,7 @@ impl Projection {
         let left_extrinsics_position = self
             .saved_parameters
             .camera_calib
-            .map(|c| c.left.extrinsics.position.map(|x| x as f32))
+            .map(|c| c.eyes()[0].extrinsics.position.map(|x| x as f32))
             .unwrap_or_default();
         // Camera space to HMD space transform, based on physical measurements
         let left_cam: Matrix4<_> = matrix![
@@ -180,7 +182,7 @@ impl Projection {
         let right_extrinsics_position = self
             .saved_parameters
             .camera_calib
-            .map(|c| c.right.extrinsics.position.map(|x| x as f32))
+            .map(|c| c.eyes()[1].extrinsics.position.map(|x| x as f32))
             .unwrap_or_default();
         let right_cam: Matrix4<_> = matrix![
             1.0, 0.0, 0.0, -right_extrinsics_position[0];
@@ -206,18 +208,19 @@ impl Projection {
             .try_inverse()
             .expect("HMD transform not invertable?");
 
-        // X gets fov / 2.0 because the source texture is a side-by-side stereo texture
+        // X gets fov / 2.0 if the source texture is a side-by-side stereo texture
         // X translation element is used to map them to left/right side of the texture,
         // respectively.
         //
+        let view_width = if self.mono { 1.0 } else { 0.5 };
         let camera_projection_left = matrix![
-            fov[0][0] / 2.0, 0.0, 0.0, 0.0;
+            fov[0][0] * view_width, 0.0, 0.0, 0.0;
             0.0, fov[0][1], 0.0, 0.0;
             0.0, 0.0, -1.0, 0.0;
             0.0, 0.0, 0.0, 1.0;
         ];
         let camera_projection_right = matrix![
-            fov[1][0] / 2.0, 0.0, 0.0, 0.0;
+            fov[1][0] * view_width, 0.0, 0.0, 0.0;
             0.0, fov[1][1] , 0.0, 0.0;
             0.0, 0.0, -1.0, 0.0;
             0.0, 0.0, 0.0, 1.0;
@@ -243,6 +246,13 @@ impl Projection {
         self.saved_parameters.mvps = mvps;
         self.mvps_changed = true;
     }
+    pub fn set_overlay_width(&mut self, overlay_width: f32) {
+        if self.saved_parameters.overlay_width == overlay_width {
+            return;
+        }
+        self.saved_parameters.overlay_width = overlay_width;
+        self.overlay_width_changed = true;
+    }
     pub fn set_mode(&mut self, mode: ProjectionMode) {
         if self.saved_parameters.mode == mode {
             return;
@@ -251,16 +261,16 @@ impl Projection {
         self.mode_ipd_changed = true;
     }
     pub fn recalculate_uniforms(&mut self) -> Result<(), ProjectorError> {
-        if !self.mode_ipd_changed && !self.mvps_changed {
+        if !self.mode_ipd_changed && !self.mvps_changed && !self.overlay_width_changed {
             return Ok(());
         }
 
         let ProjectionParameters {
             mode,
             ipd,
+            overlay_width,
             mvps,
             camera_calib,
-            ..
         } = &self.saved_parameters;
         let mut transforms_write = self
             .uniforms
@@ -271,7 +281,7 @@ impl Projection {
             .into_inner();
         if self.mode_ipd_changed {
             let left_extrinsics_position = camera_calib
-                .map(|c| c.left.extrinsics.position)
+                .map(|c| c.eyes()[0].extrinsics.position)
                 .unwrap_or_default();
             let eye_offset_left = if *mode == ProjectionMode::FromEye {
                 [
@@ -292,6 +302,12 @@ impl Projection {
             }
             self.mvps_changed = false;
         }
+        if self.overlay_width_changed {
+            for write in transforms_write.iter_mut() {
+                write.overlayWidth = (*overlay_width).into();
+            }
+            self.overlay_width_changed = false;
+        }
 
         Ok(())
     }
@@ -321,12 +337,13 @@ impl Projection {
         descriptor_set_allocator: Arc<dyn DescriptorSetAllocator>,
         source: &Arc<Image>,
         overlay_width: f32,
-        camera_calib: &Option<crate::vrapi::StereoCamera>,
+        camera_calib: &Option<crate::vrapi::CameraCalibration>,
+        mono: bool,
         final_layout: ImageLayout,
     ) -> Result<Self, ProjectorError> {
         let [w, h, _] = source.extent();
-        if w != h * 2 {
-            return Err(ProjectorError::NotSquare(w, h));
+        if !mono && w % 2 != 0 {
+            return Err(ProjectorError::NotSideBySide(w, h));
         }
         let vs = vs::load(device.clone())?;
         let fs = fs::load(device.clone())?;
@@ -346,12 +363,15 @@ impl Projection {
             }
         )
         .unwrap();
+        // Both eyes sample the whole texture if there is only one view
+        let view_width = if mono { 1.0 } else { 0.5 };
         let tex_offsets = (0..2)
             .map(|i| {
                 Self::make_uniform_buffer(
                     allocator.clone(),
                     fs::Info {
-                        texOffset: [0.5 * (i as f32), 0.0],
+                        texOffset: [if mono { 0.0 } else { 0.5 * (i as f32) }, 0.0],
+                        viewWidth: view_width,
                     },
                 )
             })
@@ -444,16 +464,16 @@ impl Projection {
             })
             .collect::<Result<Array<_, 2>, _>>()?
             .into_inner();
-        let source_extent = source.extent();
         Ok(Self {
             saved_parameters: init_params,
             uniforms: Uniforms { transforms },
             desc_sets,
             render_pass,
             pipeline,
-            extent: [source_extent[0], source_extent[1]],
+            mono,
             mode_ipd_changed: true,
             mvps_changed: true,
+            overlay_width_changed: false,
         })
     }
     pub fn project(
@@ -476,7 +496,8 @@ impl Projection {
             },
         )?;
         let ProjectionParameters { overlay_width, .. } = &self.saved_parameters;
-        let [w, h] = self.extent;
+        // The output always has a view for each eye, side by side
+        let [w, h, _] = output.extent();
         let mut cmdbuf = RecordingCommandBuffer::new(
             cmdbuf_allocator,
             queue.queue_family_index(),
@@ -0,0 +1,231 @@
+//! Recording and replaying of raw camera streams.
+//!
+//! Recordings are stored in a simple container, all integers are little endian:
+//!
+//! ```text
+//! header: magic (8 bytes) | version: u32 | width: u32 | height: u32 | fourcc: [u8; 4] | fps: u32
+//! frame:  timestamp in nanoseconds: u64 | sequence: u32 | length: u32 | data: [u8; length]
+//! ```
+//!
+//! The header is followed by any number of frames, until the end of the file.
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::camera::{Frame, FrameFormat, FrameSource};
+
+const MAGIC: &[u8; 8] = b"ICPREC\0\0";
+const VERSION: u32 = 1;
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Writes camera frames into a recording.
+pub(crate) struct Recorder {
+    writer: BufWriter<File>,
+    frames: u64,
+}
+
+impl Recorder {
+    pub(crate) fn create(path: &Path, format: &FrameFormat) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| anyhow!("cannot create recording {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&format.width.to_le_bytes())?;
+        writer.write_all(&format.height.to_le_bytes())?;
+        writer.write_all(&format.fourcc.repr)?;
+        writer.write_all(&format.fps.to_le_bytes())?;
+        log::info!("recording camera frames to {}", path.display());
+        Ok(Self { writer, frames: 0 })
+    }
+    pub(crate) fn write(&mut self, frame: &Frame<'_>) -> Result<()> {
+        let timestamp = u64::try_from(frame.timestamp.as_nanos())?;
+        let length = u32::try_from(frame.data.len())?;
+        self.writer.write_all(&timestamp.to_le_bytes())?;
+        self.writer.write_all(&frame.sequence.to_le_bytes())?;
+        self.writer.write_all(&length.to_le_bytes())?;
+        self.writer.write_all(frame.data)?;
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.writer.flush() {
+            log::error!("cannot flush recording: {e}");
+        }
+        log::info!("recorded {} frames", self.frames);
+    }
+}
+
+/// Plays back a recording made by [`Recorder`]. When the end of the recording is
+/// reached, it starts over from the beginning.
+pub(crate) struct ReplaySource {
+    reader: BufReader<File>,
+    format: FrameFormat,
+    /// Where the first frame starts in the file.
+    data_start: u64,
+    /// Whether to pace the frames as they were recorded, or play them back as fast
+    /// as possible.
+    realtime: bool,
+    buffer: Vec<u8>,
+    /// Timestamp of the first frame, and the instant it was played back.
+    first_frame: Option<(Duration, Instant)>,
+    /// Added to the recorded timestamps, so they keep increasing after we loop back to
+    /// the start.
+    timestamp_offset: Duration,
+    last_timestamp: Duration,
+}
+
+impl ReplaySource {
+    pub(crate) fn open(path: &Path, realtime: bool) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| anyhow!("cannot open recording {}", path.display()))?;
+        let mut reader = BufReader::new(file);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(anyhow!("{} is not a camera recording", path.display()));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(anyhow!("unsupported recording version {version}"));
+        }
+        let width = read_u32(&mut reader)?;
+        let height = read_u32(&mut reader)?;
+        let mut fourcc = [0u8; 4];
+        reader.read_exact(&mut fourcc)?;
+        let fps = read_u32(&mut reader)?;
+        let format = FrameFormat {
+            width,
+            height,
+            fourcc: v4l::FourCC::new(&fourcc),
+            fps,
+        };
+        log::info!("replaying {}, format: {format}", path.display());
+        let data_start = reader.stream_position()?;
+        Ok(Self {
+            reader,
+            format,
+            data_start,
+            realtime,
+            buffer: Vec::new(),
+            first_frame: None,
+            timestamp_offset: Duration::ZERO,
+            last_timestamp: Duration::ZERO,
+        })
+    }
+    /// Read the next frame into `buffer`, returns its timestamp and sequence, or `None` at
+    /// the end of the recording. A frame that is cut off, like when the recording was
+    /// killed, is the end of it too.
+    fn read_frame(&mut self) -> Result<Option<(Duration, u32)>> {
+        let is_eof = |e: &std::io::Error| e.kind() == std::io::ErrorKind::UnexpectedEof;
+        let reader = &mut self.reader;
+        let header = (|| -> std::io::Result<_> {
+            Ok((read_u64(reader)?, read_u32(reader)?, read_u32(reader)?))
+        })();
+        let (timestamp, sequence, length) = match header {
+            Ok(header) => header,
+            Err(e) if is_eof(&e) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // No frame is bigger than uncompressed RGBA, anything more is a corrupted recording
+        let max_length = self.format.width as u64 * self.format.height as u64 * 4;
+        if u64::from(length) > max_length {
+            return Err(anyhow!(
+                "frame of {length} bytes in recording, at most {max_length} expected for {}",
+                self.format
+            ));
+        }
+        self.buffer.resize(length as usize, 0);
+        match self.reader.read_exact(&mut self.buffer) {
+            Ok(()) => (),
+            Err(e) if is_eof(&e) => {
+                log::debug!("last frame of the recording is cut off");
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(Some((Duration::from_nanos(timestamp), sequence)))
+    }
+}
+
+impl FrameSource for ReplaySource {
+    fn supported_formats(&self) -> Result<Vec<FrameFormat>> {
+        Ok(vec![self.format])
+    }
+    fn negotiate_format(&mut self, requested: &FrameFormat) -> Result<FrameFormat> {
+        if requested != &self.format {
+            log::warn!(
+                "requested format {requested} doesn't match recording format {}",
+                self.format
+            );
+        }
+        Ok(self.format)
+    }
+    fn start(&mut self) -> Result<()> {
+        self.first_frame = None;
+        Ok(())
+    }
+    fn next_frame(&mut self) -> Result<Frame<'_>> {
+        let (timestamp, sequence) = match self.read_frame()? {
+            Some(header) => header,
+            None => {
+                log::debug!("end of recording, starting over");
+                self.reader
+                    .seek(std::io::SeekFrom::Start(self.data_start))?;
+                let frame_interval = if self.format.fps == 0 {
+                    Duration::ZERO
+                } else {
+                    Duration::from_secs(1) / self.format.fps
+                };
+                self.first_frame = None;
+                let header = self
+                    .read_frame()?
+                    .with_context(|| anyhow!("recording has no frames"))?;
+                self.timestamp_offset =
+                    (self.last_timestamp + frame_interval).saturating_sub(header.0);
+                header
+            }
+        };
+        let timestamp = timestamp + self.timestamp_offset;
+        if self.realtime {
+            let (first_timestamp, first_instant) =
+                *self.first_frame.get_or_insert((timestamp, Instant::now()));
+            let deadline = first_instant + timestamp.saturating_sub(first_timestamp);
+            let now = Instant::now();
+            if deadline > now {
+                std::thread::sleep(deadline - now);
+            }
+        }
+        self.last_timestamp = timestamp;
+        Ok(Frame {
+            data: &self.buffer,
+            timestamp,
+            sequence,
+            monotonic: false,
+            latency: None,
+            error: false,
+        })
+    }
+    fn stop(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+//! Save the camera images being shown.
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Context, Result};
+use image::{DynamicImage, ImageFormat, RgbImage, RgbaImage};
+
+use crate::{camera::FrameFormat, config::PixelFormat};
+
+/// Convert a YUYV frame into RGB, the same way the pipeline does.
+fn yuyv_to_rgb(data: &[u8]) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(data.len() / 2 * 3);
+    for yuyv in data.chunks_exact(4) {
+        let u = yuyv[1] as f32 / 255.0 - 0.5;
+        let v = yuyv[3] as f32 / 255.0 - 0.5;
+        for y in [yuyv[0], yuyv[2]] {
+            let y = 1.164 * (y as f32 / 255.0 - 0.0625);
+            let pixel = [y + 1.596 * v, y - 0.392 * u - 0.813 * v, y + 2.017 * u];
+            rgb.extend(pixel.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+        }
+    }
+    rgb
+}
+
+/// Save `frame` from the camera named `name` as a PNG file in `dir`, which is created if
+/// needed. `frame` is what the pipeline takes: YUYV as it is, anything else as RGBA.
+/// Returns the path of the file.
+pub(crate) fn save(dir: &Path, name: &str, format: &FrameFormat, frame: &[u8]) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| anyhow!("cannot create {}", dir.display()))?;
+    let time = humantime_serde::re::humantime::format_rfc3339_seconds(SystemTime::now());
+    let path = dir.join(format!("{name}-{time}.png"));
+    let image = if format.fourcc == PixelFormat::YUYV.fourcc() {
+        RgbImage::from_raw(format.width, format.height, yuyv_to_rgb(frame))
+            .map(DynamicImage::ImageRgb8)
+    } else {
+        RgbaImage::from_raw(format.width, format.height, frame.to_vec())
+            .map(DynamicImage::ImageRgba8)
+    };
+    let image = image.with_context(|| anyhow!("frame is too short for {format}"))?;
+    image
+        .save_with_format(&path, ImageFormat::Png)
+        .with_context(|| anyhow!("cannot write {}", path.display()))?;
+    Ok(path)
+}
@@ -0,0 +1,362 @@
+//! Frame source generating stereo frames of known content, so the lens correction and
+//! projection can be checked without a camera.
+//!
+//! The `Grid` pattern is rendered through the fisheye model [`StereoCorrection`] undoes,
+//! using [`calibration`]. When the pipeline is set up with the same calibration, the grid
+//! lines come out straight, and a point `d` meters in front of the HMD shows up where the
+//! projection puts things `d` meters away.
+//!
+//! [`StereoCorrection`]: crate::distortion_correction::StereoCorrection
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    camera::{Frame, FrameFormat, FrameSource},
+    config::{PixelFormat, TestPattern},
+    vrapi::{Camera, Distort, Extrinsics, Intrinsics, StereoCamera, TrackedCamera},
+};
+
+/// Half the size of the box around the HMD drawn by the `Grid` pattern, in meters. The
+/// front wall is further away, to have something at a different distance.
+const BOX_SIZE: [f64; 3] = [1.0, 1.0, 2.0];
+/// Distance between grid lines, in meters.
+const GRID_SPACING: f64 = 0.25;
+const LINE_WIDTH: f64 = 0.006;
+
+/// Calibration of the cameras the `Grid` pattern is seen through, close to the Index's.
+pub(crate) fn calibration() -> StereoCamera {
+    let camera = |name, x, center: [f64; 2]| TrackedCamera {
+        extrinsics: Extrinsics {
+            position: [x, 0.0075, -0.06],
+        },
+        intrinsics: Intrinsics {
+            center_x: center[0],
+            center_y: center[1],
+            focal_x: 395.0,
+            focal_y: 395.0,
+            width: 960.0,
+            height: 960.0,
+            distort: Distort {
+                coeffs: [0.08, -0.02, 0.005, -0.0005],
+            },
+        },
+        name,
+    };
+    StereoCamera {
+        // Optical centers are a bit off the middle, so not accounting for them shows
+        left: camera(Camera::Left, -0.0335, [484.0, 472.0]),
+        right: camera(Camera::Right, 0.0335, [476.0, 488.0]),
+    }
+}
+
+/// Angle from the optical axis of a ray that the fisheye lens maps to `distorted` focal
+/// lengths away from the optical center, i.e. the inverse of the distortion applied in
+/// `stereo_correction.frag`.
+fn undistort(coeffs: &[f64; 4], distorted: f64) -> Option<f64> {
+    let [k1, k2, k3, k4] = *coeffs;
+    let mut theta = distorted;
+    for _ in 0..50 {
+        let theta2 = theta * theta;
+        let f =
+            theta * (1.0 + theta2 * (k1 + theta2 * (k2 + theta2 * (k3 + theta2 * k4)))) - distorted;
+        let fp = 1.0
+            + theta2 * (3.0 * k1 + theta2 * (5.0 * k2 + theta2 * (7.0 * k3 + theta2 * 9.0 * k4)));
+        if fp <= 0.0 {
+            return None;
+        }
+        let step = f / fp;
+        theta -= step;
+        if step.abs() < 1e-9 {
+            return Some(theta);
+        }
+    }
+    None
+}
+
+/// Direction of the ray the fisheye lens maps to `offset` focal lengths away from the
+/// optical center, with Y pointing down in the image. `None` if no ray in front of the
+/// camera is mapped there.
+fn ray_direction(coeffs: &[f64; 4], offset: [f64; 2]) -> Option<[f64; 3]> {
+    let distorted = offset[0].hypot(offset[1]);
+    let theta =
+        undistort(coeffs, distorted).filter(|theta| *theta < std::f64::consts::FRAC_PI_2)?;
+    let scale = if distorted > 0.0 {
+        theta.tan() / distorted
+    } else {
+        0.0
+    };
+    // The camera looks down -Z like the HMD, with Y pointing down in the image
+    Some([offset[0] * scale, -offset[1] * scale, -1.0])
+}
+
+/// Color of the box around the HMD where the ray from `origin` in `direction` hits it.
+/// Both are in the HMD's space, in meters.
+fn grid_color(origin: [f64; 3], direction: [f64; 3]) -> [u8; 3] {
+    // Distance along the ray to the wall it hits on each axis
+    let hits = [0, 1, 2].map(|axis| {
+        let bound = if direction[axis] > 0.0 {
+            BOX_SIZE[axis]
+        } else {
+            -BOX_SIZE[axis]
+        };
+        (bound - origin[axis]) / direction[axis]
+    });
+    let (wall, t) = hits
+        .into_iter()
+        .enumerate()
+        .filter(|(_, t)| t.is_finite() && *t > 0.0)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap();
+    let hit = [0, 1, 2].map(|axis| origin[axis] + direction[axis] * t);
+    let on_line = |x: f64| {
+        let offset = (x / GRID_SPACING).round() * GRID_SPACING - x;
+        offset.abs() < LINE_WIDTH
+    };
+    let axes = [0, 1, 2].into_iter().filter(|axis| *axis != wall);
+    if axes.clone().any(|axis| hit[axis].abs() < LINE_WIDTH * 2.0) {
+        // Lines through the point straight in front of, and level with the HMD
+        [255, 220, 0]
+    } else if axes.clone().any(|axis| on_line(hit[axis])) {
+        [255, 255, 255]
+    } else {
+        match (wall, direction[wall] > 0.0) {
+            // left and right walls
+            (0, false) => [110, 40, 40],
+            (0, true) => [40, 110, 40],
+            // floor and ceiling
+            (1, false) => [40, 50, 110],
+            (1, true) => [90, 90, 90],
+            _ => [50, 50, 50],
+        }
+    }
+}
+
+/// Render what `camera` sees of the box around the HMD, into a view of `width` x
+/// `height` pixels.
+fn grid_view(camera: &TrackedCamera, width: u32, height: u32) -> Vec<[u8; 3]> {
+    // Calibration is in pixels of the resolution the camera was calibrated at
+    let intrinsics = &camera.intrinsics;
+    let center = [
+        intrinsics.center_x / intrinsics.width,
+        intrinsics.center_y / intrinsics.height,
+    ];
+    let focal = [
+        intrinsics.focal_x / intrinsics.width,
+        intrinsics.focal_y / intrinsics.height,
+    ];
+    let mut view = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            // Offset from the optical center, in focal lengths
+            let offset = [
+                ((x as f64 + 0.5) / width as f64 - center[0]) / focal[0],
+                ((y as f64 + 0.5) / height as f64 - center[1]) / focal[1],
+            ];
+            let color = ray_direction(&intrinsics.distort.coeffs, offset)
+                .map_or([0, 0, 0], |direction| {
+                    grid_color(camera.extrinsics.position, direction)
+                });
+            view.push(color);
+        }
+    }
+    view
+}
+
+fn checkerboard_view(width: u32, height: u32) -> Vec<[u8; 3]> {
+    let square = (height / 12).max(1) as i64;
+    let (center_x, center_y) = (width as i64 / 2, height as i64 / 2);
+    (0..height as i64)
+        .flat_map(|y| (0..width as i64).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            if ((x - center_x).div_euclid(square) + (y - center_y).div_euclid(square)) % 2 == 0 {
+                [235, 235, 235]
+            } else {
+                [20, 20, 20]
+            }
+        })
+        .collect()
+}
+
+fn color_bars_view(width: u32, height: u32) -> Vec<[u8; 3]> {
+    const BARS: [[u8; 3]; 7] = [
+        [191, 191, 191],
+        [191, 191, 0],
+        [0, 191, 191],
+        [0, 191, 0],
+        [191, 0, 191],
+        [191, 0, 0],
+        [0, 0, 191],
+    ];
+    (0..height)
+        .flat_map(|_| (0..width).map(|x| BARS[(x * BARS.len() as u32 / width) as usize]))
+        .collect()
+}
+
+/// Convert RGB pixels to YUYV, the inverse of what `yuyv2rgb.frag` does.
+fn to_yuyv(pixels: &[[u8; 3]]) -> Vec<u8> {
+    let yuv = |[r, g, b]: [u8; 3]| {
+        let [r, g, b] = [r, g, b].map(|x| x as f32);
+        [
+            16.0 + 0.257 * r + 0.504 * g + 0.098 * b,
+            128.0 - 0.148 * r - 0.291 * g + 0.439 * b,
+            128.0 + 0.439 * r - 0.368 * g - 0.071 * b,
+        ]
+    };
+    pixels
+        .chunks_exact(2)
+        .flat_map(|pair| {
+            let [y0, u0, v0] = yuv(pair[0]);
+            let [y1, u1, v1] = yuv(pair[1]);
+            [y0, (u0 + u1) / 2.0, y1, (v0 + v1) / 2.0].map(|x| x.round() as u8)
+        })
+        .collect()
+}
+
+/// Generates the same frame over and over, at a fixed rate.
+pub(crate) struct PatternSource {
+    format: FrameFormat,
+    frame: Vec<u8>,
+    sequence: u32,
+    /// when the next frame is due
+    deadline: Option<Instant>,
+}
+
+impl PatternSource {
+    /// Render `pattern` into side-by-side stereo frames of `width` x `height`.
+    pub(crate) fn new(pattern: TestPattern, width: u32, height: u32, fps: u32) -> Result<Self> {
+        if width == 0 || width % 4 != 0 || height == 0 || fps == 0 {
+            return Err(anyhow!(
+                "invalid test pattern format {width}x{height}@{fps}, width must be a \
+                 multiple of 4"
+            ));
+        }
+        let view_width = width / 2;
+        let calibration = calibration();
+        let views = [calibration.left, calibration.right].map(|camera| match pattern {
+            TestPattern::Checkerboard => checkerboard_view(view_width, height),
+            TestPattern::ColorBars => color_bars_view(view_width, height),
+            TestPattern::Grid => grid_view(&camera, view_width, height),
+        });
+        let pixels = views[0]
+            .chunks_exact(view_width as usize)
+            .zip(views[1].chunks_exact(view_width as usize))
+            .flat_map(|(left, right)| left.iter().chain(right))
+            .copied()
+            .collect::<Vec<_>>();
+        let format = FrameFormat {
+            width,
+            height,
+            fourcc: PixelFormat::YUYV.fourcc(),
+            fps,
+        };
+        log::info!("generating {pattern:?} test pattern, format: {format}");
+        Ok(Self {
+            format,
+            frame: to_yuyv(&pixels),
+            sequence: 0,
+            deadline: None,
+        })
+    }
+}
+
+impl FrameSource for PatternSource {
+    fn supported_formats(&self) -> Result<Vec<FrameFormat>> {
+        Ok(vec![self.format])
+    }
+    fn negotiate_format(&mut self, requested: &FrameFormat) -> Result<FrameFormat> {
+        if requested != &self.format {
+            log::warn!(
+                "requested format {requested} doesn't match test pattern format {}",
+                self.format
+            );
+        }
+        Ok(self.format)
+    }
+    fn start(&mut self) -> Result<()> {
+        self.deadline = None;
+        Ok(())
+    }
+    fn next_frame(&mut self) -> Result<Frame<'_>> {
+        let now = Instant::now();
+        let deadline = *self.deadline.get_or_insert(now);
+        if deadline > now {
+            std::thread::sleep(deadline - now);
+        }
+        // Don't try to catch up if we fell behind
+        let interval = Duration::from_secs(1) / self.format.fps;
+        self.deadline = Some((deadline + interval).max(now));
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(Frame {
+            data: &self.frame,
+            timestamp: crate::clock::monotonic_now(),
+            sequence: self.sequence,
+            monotonic: true,
+            latency: None,
+            error: false,
+        })
+    }
+    fn stop(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where `stereo_correction.frag` samples the camera image for a point `corrected` focal
+    /// lengths away from the optical center in its output, in focal lengths from the optical
+    /// center of the camera image.
+    fn distort(coeffs: &[f64; 4], corrected: [f64; 2]) -> [f64; 2] {
+        let [k1, k2, k3, k4] = *coeffs;
+        let r = corrected[0].hypot(corrected[1]);
+        if r == 0.0 {
+            return corrected;
+        }
+        let theta = r.atan();
+        let theta2 = theta * theta;
+        let theta = theta * (1.0 + theta2 * (k1 + theta2 * (k2 + theta2 * (k3 + theta2 * k4))));
+        corrected.map(|x| x * theta / r)
+    }
+
+    /// Points on the grid lines of the front wall, taken through the correction
+    /// `stereo_correction.frag` does and back through the lens model [`grid_view`] renders
+    /// with, land where they started. So the grid comes out straight.
+    #[test]
+    fn grid_comes_out_straight() {
+        let calibration = calibration();
+        for camera in [&calibration.left, &calibration.right] {
+            let coeffs = &camera.intrinsics.distort.coeffs;
+            let origin = camera.extrinsics.position;
+            let wall = -BOX_SIZE[2];
+            for line in -3..=3 {
+                let line = line as f64 * GRID_SPACING;
+                for step in -12..=12 {
+                    let along = step as f64 * GRID_SPACING / 4.0;
+                    // Points along a vertical and a horizontal grid line
+                    for point in [[line, along], [along, line]] {
+                        // Straight lines stay straight in the corrected view, a pinhole
+                        // projection
+                        let depth = origin[2] - wall;
+                        let corrected = [
+                            (point[0] - origin[0]) / depth,
+                            -(point[1] - origin[1]) / depth,
+                        ];
+                        let direction = ray_direction(coeffs, distort(coeffs, corrected))
+                            .expect("grid point is in view");
+                        let t = (wall - origin[2]) / direction[2];
+                        for axis in [0, 1] {
+                            let error = origin[axis] + direction[axis] * t - point[axis];
+                            assert!(
+                                error.abs() < 1e-6,
+                                "{:?} camera: grid point {point:?} is off by {error} m",
+                                camera.name
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -31,25 +31,26 @@ use vulkano::{
     Handle, VulkanObject,
 };
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    config::{DisplayMode, Eye, PositionMode},
+    config::{BindingsConfig, DisplayMode, Eye, PositionMode},
     utils::DeviceExt,
-    APP_KEY, APP_NAME, CAMERA_SIZE,
+    APP_KEY, APP_NAME,
 };
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Debug)]
 pub struct Extrinsics {
     /// Offset of the camera from Hmd
     pub position: [f64; 3],
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Debug)]
 pub struct Distort {
     pub coeffs: [f64; 4],
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Debug)]
 pub struct Intrinsics {
     /// Optical center X
     pub center_x: f64,
@@ -84,6 +85,32 @@ pub struct StereoCamera {
     pub left: TrackedCamera,
     pub right: TrackedCamera,
 }
+
+/// Calibration of the camera the passthrough image comes from.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CameraCalibration {
+    /// Two cameras, their views side by side
+    Stereo(StereoCamera),
+    /// A single camera, both eyes see the same view
+    Mono(TrackedCamera),
+}
+
+impl CameraCalibration {
+    /// Cameras in the order of their views in the image.
+    pub fn views(&self) -> Vec<&TrackedCamera> {
+        match self {
+            Self::Stereo(stereo) => vec![&stereo.left, &stereo.right],
+            Self::Mono(camera) => vec![camera],
+        }
+    }
+    /// The camera each eye sees through, left then right.
+    pub fn eyes(&self) -> [&TrackedCamera; 2] {
+        match self {
+            Self::Stereo(stereo) => [&stereo.left, &stereo.right],
+            Self::Mono(camera) => [camera, camera],
+        }
+    }
+}
 pub struct Bounds {
     pub umin: f32,
     pub vmin: f32,
@@ -91,17 +118,107 @@ pub struct Bounds {
     pub vmax: f32,
 }
 
+/// An overlay showing one camera's image.
+#[derive(Clone, Debug)]
+pub(crate) struct OverlayInfo {
+    /// name of the camera, used to tell the overlays apart
+    pub name: String,
+    /// size of the overlay texture, two views side by side
+    pub extent: [u32; 2],
+    /// the camera only has one view, which is put in the left half of the overlay texture
+    /// unless it's projected
+    pub mono: bool,
+    /// the camera is the HMD's, so the HMD's camera calibration applies to it
+    pub hmd_camera: bool,
+}
+
 pub enum Event {
     /// The VR API asks us to exit
     RequestExit,
+    /// The play area was set up again, `Vr::tracking_universe` may have changed
+    TrackingUniverseChanged,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize, JsonSchema,
+)]
 pub enum Action {
+    /// one of the two buttons pressed together to open and close the overlay
     Button1 = 0,
+    /// the other button to open and close the overlay
     Button2 = 1,
+    /// take a renderdoc capture, if `debug` is on
     Debug = 2,
+    /// move a "Sticky" overlay in front of you
     Reposition = 3,
+    /// switch to the next display profile
+    NextProfile = 4,
+    /// switch the main camera to the next display mode: flat with the left camera, flat
+    /// with the right camera, then stereo
+    CycleDisplayMode = 5,
+    /// switch the main camera's stereo projection between "FromCamera" and "FromEye"
+    ToggleProjectionMode = 6,
+    /// save the image of each camera being shown
+    Snapshot = 7,
+    /// make the main camera's overlay bigger
+    SizeUp = 8,
+    /// make the main camera's overlay smaller
+    SizeDown = 9,
+    /// make the main camera's overlay more opaque
+    OpacityUp = 10,
+    /// make the main camera's overlay more transparent
+    OpacityDown = 11,
+}
+
+impl Action {
+    pub const ALL: [Self; 12] = [
+        Self::Button1,
+        Self::Button2,
+        Self::Debug,
+        Self::Reposition,
+        Self::NextProfile,
+        Self::CycleDisplayMode,
+        Self::ToggleProjectionMode,
+        Self::Snapshot,
+        Self::SizeUp,
+        Self::SizeDown,
+        Self::OpacityUp,
+        Self::OpacityDown,
+    ];
+    /// Name of the action in the OpenVR action manifest and the OpenXR action set.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Button1 => "button1",
+            Self::Button2 => "button2",
+            Self::Debug => "debug",
+            Self::Reposition => "reposition",
+            Self::NextProfile => "next_profile",
+            Self::CycleDisplayMode => "cycle_display_mode",
+            Self::ToggleProjectionMode => "toggle_projection_mode",
+            Self::Snapshot => "snapshot",
+            Self::SizeUp => "size_up",
+            Self::SizeDown => "size_down",
+            Self::OpacityUp => "opacity_up",
+            Self::OpacityDown => "opacity_down",
+        }
+    }
+    /// Name of the action shown to the user, e.g. in the SteamVR bindings UI.
+    pub fn localized_name(self) -> &'static str {
+        match self {
+            Self::Button1 => "Button 1",
+            Self::Button2 => "Button 2",
+            Self::Debug => "Debug",
+            Self::Reposition => "Reposition",
+            Self::NextProfile => "Next profile",
+            Self::CycleDisplayMode => "Cycle display mode",
+            Self::ToggleProjectionMode => "Toggle projection mode",
+            Self::Snapshot => "Snapshot",
+            Self::SizeUp => "Bigger overlay",
+            Self::SizeDown => "Smaller overlay",
+            Self::OpacityUp => "More opaque overlay",
+            Self::OpacityDown => "More transparent overlay",
+        }
+    }
 }
 
 pub(crate) trait VkContext {
@@ -112,10 +229,13 @@ pub(crate) trait VkContext {
     fn vk_command_buffer_allocator(&self) -> Arc<dyn CommandBufferAllocator>;
 }
 
+/// A VR backend, showing one overlay for each camera.
+///
+/// `overlay` arguments are indices into the overlays the backend was created with.
 pub(crate) trait Vr: VkContext {
     type Error: Send + Sync + 'static;
-    fn load_camera_paramter(&mut self) -> Option<StereoCamera>;
-    fn set_fallback_camera_config(&mut self, cfg: StereoCamera);
+    fn load_camera_paramter(&mut self, overlay: usize) -> Option<CameraCalibration>;
+    fn set_fallback_camera_config(&mut self, overlay: usize, cfg: CameraCalibration);
     /// Submit the render texture to overlay.
     ///
     /// Must have called `render_texture` before calling this function. The render texture must have
@@ -123,29 +243,55 @@ pub(crate) trait Vr: VkContext {
     ///
     /// # Arguments
     ///
-    /// - `elapsed`: duration since the image was captured.
-    fn submit_texture(&mut self, elapsed: Duration, fov: &[[f32; 2]; 2])
-        -> Result<(), Self::Error>;
-    /// Refresh the overlay using the latest submitted camera texture.
+    /// - `capture_time`: when the image was captured, in `CLOCK_MONOTONIC`.
+    fn submit_texture(
+        &mut self,
+        overlay: usize,
+        capture_time: Duration,
+        fov: &[[f32; 2]; 2],
+    ) -> Result<(), Self::Error>;
+    /// Finish the frame after the textures of the overlays with new camera images are
+    /// submitted. The other overlays keep showing their last texture.
+    fn end_frame(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Refresh the overlays using the latest submitted camera textures.
     fn refresh(&mut self) -> Result<(), Self::Error>;
     /// Whether our render loop is synchronized with the VR runtime.
     ///
-    /// If this is true, `get_render_texture`, `submit_texture` and `refresh` should be synchronized with
-    /// the VR runtime.
+    /// If this is true, `get_render_texture`, `submit_texture`, `end_frame` and `refresh` should be
+    /// synchronized with the VR runtime.
     fn is_synchronized(&self) -> bool;
-    fn set_position_mode(&mut self, mode: PositionMode) -> Result<(), Self::Error>;
+    /// Set how the overlay is positioned. A `Sticky` overlay without a transform is placed
+    /// in front of the HMD when the next texture is submitted.
+    fn set_position_mode(&mut self, overlay: usize, mode: PositionMode) -> Result<(), Self::Error>;
+    /// How the overlay is positioned, including where a `Sticky` overlay was placed.
+    fn position_mode(&self, overlay: usize) -> PositionMode;
+    /// Identifies the tracking universe overlay positions are in, which changes when the
+    /// play area is set up again. `None` if the backend can't tell, overlay placements
+    /// aren't remembered then.
+    fn tracking_universe(&mut self) -> Result<Option<String>, Self::Error>;
     /// Change the display mode of the overlay.
     ///
     /// This invalidates previously returned render texture.
-    fn set_display_mode(&mut self, mode: DisplayMode) -> Result<(), Self::Error>;
+    fn set_display_mode(&mut self, overlay: usize, mode: DisplayMode) -> Result<(), Self::Error>;
+    /// Set the z order of the overlays, higher is on top of other overlays. Returns false
+    /// if the backend can't change it once the overlays are created.
+    fn set_z_order(&mut self, z_order: u32) -> Result<bool, Self::Error>;
+    /// Set the width of the overlay, in meters.
+    fn set_size(&mut self, overlay: usize, size: f32) -> Result<(), Self::Error>;
+    /// Set the opacity of the overlay, from 0 to 1. Returns false if the backend can't.
+    fn set_opacity(&mut self, overlay: usize, opacity: f32) -> Result<bool, Self::Error>;
+    /// Show all the overlays.
     fn show_overlay(&mut self) -> Result<(), Self::Error>;
+    /// Hide all the overlays.
     fn hide_overlay(&mut self) -> Result<(), Self::Error>;
     fn acknowledge_quit(&mut self);
     /// Acquire a render texture that can be submitted to the overlay.
     ///
     /// Once this is called, the render texture is considered acquired until it's released by calling `submit_texture`.
     /// Caller should upload camera frames to the render texture, then call `submit_texture`.
-    fn get_render_texture(&mut self) -> Result<Option<Arc<Image>>, Self::Error>;
+    fn get_render_texture(&mut self, overlay: usize) -> Result<Option<Arc<Image>>, Self::Error>;
     fn poll_next_event(&mut self) -> Result<Option<Event>, Self::Error>;
     fn update_action_state(&mut self) -> Result<(), Self::Error>;
     fn get_action_state(&self, action: Action) -> Result<bool, Self::Error>;
@@ -179,18 +325,24 @@ impl<T: Vr, E: Send + Sync + 'static, F: Fn(<T as Vr>::Error) -> E> Vr for VrMap
     fn acknowledge_quit(&mut self) {
         self.0.acknowledge_quit()
     }
-    fn load_camera_paramter(&mut self) -> Option<StereoCamera> {
-        self.0.load_camera_paramter()
+    fn load_camera_paramter(&mut self, overlay: usize) -> Option<CameraCalibration> {
+        self.0.load_camera_paramter(overlay)
     }
-    fn set_fallback_camera_config(&mut self, cfg: StereoCamera) {
-        self.0.set_fallback_camera_config(cfg)
+    fn set_fallback_camera_config(&mut self, overlay: usize, cfg: CameraCalibration) {
+        self.0.set_fallback_camera_config(overlay, cfg)
     }
     fn submit_texture(
         &mut self,
-        elapsed: Duration,
+        overlay: usize,
+        capture_time: Duration,
         fov: &[[f32; 2]; 2],
     ) -> Result<(), Self::Error> {
-        self.0.submit_texture(elapsed, fov).map_err(&self.1)
+        self.0
+            .submit_texture(overlay, capture_time, fov)
+            .map_err(&self.1)
+    }
+    fn end_frame(&mut self) -> Result<(), Self::Error> {
+        self.0.end_frame().map_err(&self.1)
     }
     fn refresh(&mut self) -> Result<(), Self::Error> {
         self.0.refresh().map_err(&self.1)
@@ -198,17 +350,32 @@ impl<T: Vr, E: Send + Sync + 'static, F: Fn(<T as Vr>::Error) -> E> Vr for VrMap
     fn is_synchronized(&self) -> bool {
         self.0.is_synchronized()
     }
+    fn set_z_order(&mut self, z_order: u32) -> Result<bool, Self::Error> {
+        self.0.set_z_order(z_order).map_err(&self.1)
+    }
+    fn set_size(&mut self, overlay: usize, size: f32) -> Result<(), Self::Error> {
+        self.0.set_size(overlay, size).map_err(&self.1)
+    }
+    fn set_opacity(&mut self, overlay: usize, opacity: f32) -> Result<bool, Self::Error> {
+        self.0.set_opacity(overlay, opacity).map_err(&self.1)
+    }
     fn show_overlay(&mut self) -> Result<(), Self::Error> {
         self.0.show_overlay().map_err(&self.1)
     }
-    fn set_display_mode(&mut self, mode: DisplayMode) -> Result<(), Self::Error> {
-        self.0.set_display_mode(mode).map_err(&self.1)
+    fn set_display_mode(&mut self, overlay: usize, mode: DisplayMode) -> Result<(), Self::Error> {
+        self.0.set_display_mode(overlay, mode).map_err(&self.1)
+    }
+    fn set_position_mode(&mut self, overlay: usize, mode: PositionMode) -> Result<(), Self::Error> {
+        self.0.set_position_mode(overlay, mode).map_err(&self.1)
+    }
+    fn position_mode(&self, overlay: usize) -> PositionMode {
+        self.0.position_mode(overlay)
     }
-    fn set_position_mode(&mut self, mode: PositionMode) -> Result<(), Self::Error> {
-        self.0.set_position_mode(mode).map_err(&self.1)
+    fn tracking_universe(&mut self) -> Result<Option<String>, Self::Error> {
+        self.0.tracking_universe().map_err(&self.1)
     }
-    fn get_render_texture(&mut self) -> Result<Option<Arc<Image>>, Self::Error> {
-        self.0.get_render_texture().map_err(&self.1)
+    fn get_render_texture(&mut self, overlay: usize) -> Result<Option<Arc<Image>>, Self::Error> {
+        self.0.get_render_texture(overlay).map_err(&self.1)
     }
     fn hide_overlay(&mut self) -> Result<(), Self::Error> {
         self.0.hide_overlay().map_err(&self.1)
@@ -246,27 +413,119 @@ struct TextureState {
     _instance: Arc<Instance>,
 }
 
-pub(crate) struct OpenVr {
-    sys: crate::openvr::VRSystem,
+/// One overlay, showing the image of one camera.
+struct OpenVrOverlay {
     handle: openvr_sys2::VROverlayHandle_t,
-    buttons: [openvr_sys2::VRActionHandle_t; 4],
-    action_set: openvr_sys2::VRActionSetHandle_t,
     texture: Option<TextureState>,
-    camera_config: Option<StereoCamera>,
+    camera_config: Option<CameraCalibration>,
     position_mode: PositionMode,
     reposition: bool,
     display_mode: DisplayMode,
     overlay_transform: Matrix4<f32>,
+    /// width of the overlay, in meters
+    size: f32,
     projector: Option<crate::projection::Projection>,
+    render_texture: Option<Arc<vulkano::image::Image>>,
+    double_buffer: [Arc<vulkano::image::Image>; 2],
+    texture_in_use: u64,
+    /// size of the overlay texture, two views side by side
+    extent: [u32; 2],
+    /// the camera only has one view, which is put in the left half of the overlay texture
+    /// unless it's projected
+    mono: bool,
+    /// the HMD's calibration applies to this camera
+    hmd_camera: bool,
+}
+
+impl OpenVrOverlay {
+    fn new(
+        sys: &crate::openvr::VRSystem,
+        device: &Arc<Device>,
+        index: usize,
+        info: &OverlayInfo,
+    ) -> Result<Self, OpenVrError> {
+        // The first overlay keeps the key it always had, so SteamVR remembers its settings
+        let handle = if index == 0 {
+            sys.overlay().create_overlay(APP_KEY, APP_NAME)?
+        } else {
+            let key = format!("{}.{}\0", APP_KEY.trim_end_matches('\0'), info.name);
+            let name = format!("{} ({})\0", APP_NAME.trim_end_matches('\0'), info.name);
+            sys.overlay().create_overlay(&key, &name)?
+        };
+        sys.overlay()
+            .pin_mut()
+            .SetOverlayTextureColorSpace(handle, openvr_sys2::EColorSpace::ColorSpace_Linear)
+            .into_result()?;
+        Ok(Self {
+            handle,
+            texture: None,
+            camera_config: None,
+            position_mode: PositionMode::default(),
+            reposition: false,
+            display_mode: DisplayMode::default(),
+            overlay_transform: Matrix4::identity(),
+            size: 1.0,
+            projector: None,
+            render_texture: None,
+            double_buffer: [0, 1].map(|_| {
+                crate::create_submittable_image(device.clone(), info.extent)
+                    .expect("create_submittable_image")
+            }),
+            texture_in_use: 1,
+            extent: info.extent,
+            mono: info.mono,
+            hmd_camera: info.hmd_camera,
+        })
+    }
+    fn set_texture_bounds(
+        &self,
+        sys: &crate::openvr::VRSystem,
+        bounds: Bounds,
+    ) -> Result<(), OpenVrError> {
+        let bounds = openvr_sys2::VRTextureBounds_t {
+            uMin: bounds.umin,
+            vMin: bounds.vmin,
+            uMax: bounds.umax,
+            vMax: bounds.vmax,
+        };
+        unsafe {
+            sys.overlay()
+                .pin_mut()
+                .SetOverlayTextureBounds(self.handle, &bounds)
+                .into_result()
+                .map_err(Into::into)
+        }
+    }
+    fn set_transformation(
+        &mut self,
+        sys: &crate::openvr::VRSystem,
+        transform: Matrix4<f32>,
+    ) -> Result<(), OpenVrError> {
+        self.overlay_transform = transform;
+        let vroverlay = sys.overlay();
+        unsafe {
+            vroverlay.pin_mut().SetOverlayTransformAbsolute(
+                self.handle,
+                openvr_sys2::ETrackingUniverseOrigin::TrackingUniverseStanding,
+                &(&transform).into(),
+            )
+        }
+        .into_result()
+        .map_err(Into::into)
+    }
+}
+
+pub(crate) struct OpenVr {
+    sys: crate::openvr::VRSystem,
+    buttons: [openvr_sys2::VRActionHandle_t; Action::ALL.len()],
+    action_set: openvr_sys2::VRActionSetHandle_t,
+    overlays: Vec<OpenVrOverlay>,
     device: Arc<Device>,
     queue: Arc<Queue>,
     instance: Arc<Instance>,
     allocator: Arc<StandardMemoryAllocator>,
     cmdbuf_allocator: Arc<StandardCommandBufferAllocator>,
     descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
-    render_texture: Option<Arc<vulkano::image::Image>>,
-    double_buffer: [Arc<vulkano::image::Image>; 2],
-    texture_in_use: u64,
     ipd: Option<f32>,
 }
 impl OpenVr {
@@ -337,15 +596,14 @@ impl OpenVr {
             },
         )?)
     }
-    pub fn new(xdg: &xdg::BaseDirectories) -> Result<Self, OpenVrError> {
+    /// `action_manifest` is the path to the action manifest written by
+    /// `bindings::write_openvr_manifest`.
+    pub fn new(
+        action_manifest: &std::path::Path,
+        overlays: &[OverlayInfo],
+    ) -> Result<Self, OpenVrError> {
         let sys = crate::openvr::VRSystem::init()?;
-        let vroverlay = sys.overlay().create_overlay(APP_KEY, APP_NAME)?;
-        sys.overlay()
-            .pin_mut()
-            .SetOverlayTextureColorSpace(vroverlay, openvr_sys2::EColorSpace::ColorSpace_Linear)
-            .into_result()?;
         let mut input = unsafe { Pin::new_unchecked(&mut *openvr_sys2::VRInput()) };
-        let action_manifest = xdg.find_data_file("actions.json").unwrap();
         let action_manifest = std::ffi::CString::new(action_manifest.to_str().unwrap()).unwrap();
         unsafe {
             input
@@ -353,36 +611,22 @@ impl OpenVr {
                 .SetActionManifestPath(action_manifest.as_ptr())
         }
         .into_result()?;
-        let mut button = [const { MaybeUninit::uninit() }; 4];
-        for i in 0..2 {
-            let name = CString::new(format!("/actions/main/in/button{}", i + 1)).unwrap();
+        let mut button = [const { MaybeUninit::uninit() }; Action::ALL.len()];
+        for (action, button) in Action::ALL.into_iter().zip(&mut button) {
+            let name = CString::new(crate::bindings::action_path(action)).unwrap();
             unsafe {
                 input
                     .as_mut()
-                    .GetActionHandle(name.as_ptr(), button[i].as_mut_ptr())
+                    .GetActionHandle(name.as_ptr(), button.as_mut_ptr())
                     .into_result()?;
             };
         }
-        unsafe {
-            let name = CString::new("/actions/main/in/debug").unwrap();
-            input
-                .as_mut()
-                .GetActionHandle(name.as_ptr(), button[2].as_mut_ptr())
-                .into_result()?;
-        }
-        unsafe {
-            let name = CString::new("/actions/main/in/reposition").unwrap();
-            input
-                .as_mut()
-                .GetActionHandle(name.as_ptr(), button[3].as_mut_ptr())
-                .into_result()?;
-        };
         let button = button.map(|b| unsafe { b.assume_init() });
 
         log::debug!("buttons: {:?}", button);
         let action_set = unsafe {
             let mut action_set = MaybeUninit::uninit();
-            let action_set_name = CString::new("/actions/main").unwrap();
+            let action_set_name = CString::new(crate::bindings::ACTION_SET).unwrap();
             input
                 .GetActionSetHandle(action_set_name.as_ptr(), action_set.as_mut_ptr())
                 .into_result()?;
@@ -400,29 +644,30 @@ impl OpenVr {
             device.clone(),
             Default::default(),
         ));
+        let mut vroverlays = Vec::with_capacity(overlays.len());
+        for (index, info) in overlays.iter().enumerate() {
+            match OpenVrOverlay::new(&sys, &device, index, info) {
+                Ok(overlay) => vroverlays.push(overlay),
+                Err(e) => {
+                    // Don't leak the overlays we already created
+                    for overlay in vroverlays {
+                        let _ = unsafe { sys.overlay().destroy_overlay_raw(overlay.handle) };
+                    }
+                    return Err(e);
+                }
+            }
+        }
         Ok(Self {
             sys,
-            handle: vroverlay,
             action_set,
             buttons: button,
-            texture: None,
-            display_mode: DisplayMode::default(),
-            position_mode: PositionMode::default(),
-            reposition: false,
-            projector: None,
-            overlay_transform: Matrix4::identity(),
-            camera_config: None,
+            overlays: vroverlays,
             instance,
             allocator,
             descriptor_set_allocator,
             queue,
             cmdbuf_allocator,
-            double_buffer: [0, 1].map(|_| {
-                crate::create_submittable_image(device.clone()).expect("create_submittable_image")
-            }),
-            texture_in_use: 1,
             device,
-            render_texture: None,
             ipd: None,
         })
     }
@@ -453,35 +698,6 @@ impl OpenVr {
             .map(|cstr| cstr.to_str().unwrap())
             .collect()
     }
-    fn set_overlay_texture_bounds_internal(&mut self, bounds: Bounds) -> Result<(), OpenVrError> {
-        let bounds = openvr_sys2::VRTextureBounds_t {
-            uMin: bounds.umin,
-            vMin: bounds.vmin,
-            uMax: bounds.umax,
-            vMax: bounds.vmax,
-        };
-        unsafe {
-            self.sys
-                .overlay()
-                .pin_mut()
-                .SetOverlayTextureBounds(self.handle, &bounds)
-                .into_result()
-                .map_err(Into::into)
-        }
-    }
-    fn set_overlay_transformation(&mut self, transform: Matrix4<f32>) -> Result<(), OpenVrError> {
-        self.overlay_transform = transform;
-        let vroverlay = self.sys.overlay();
-        unsafe {
-            vroverlay.pin_mut().SetOverlayTransformAbsolute(
-                self.handle,
-                openvr_sys2::ETrackingUniverseOrigin::TrackingUniverseStanding,
-                &(&transform).into(),
-            )
-        }
-        .into_result()
-        .map_err(Into::into)
-    }
     fn eye_to_head(&self) -> [Matrix4<f32>; 2] {
         let left_eye: Matrix4<_> = self
             .sys
@@ -529,10 +745,12 @@ pub(crate) enum OpenVrError {
 
 impl Drop for OpenVr {
     fn drop(&mut self) {
-        log::info!("Dropping overlay handle");
+        log::info!("Dropping overlay handles");
         let vroverlay = self.sys.overlay();
-        if let Err(e) = unsafe { vroverlay.destroy_overlay_raw(self.handle) } {
-            eprintln!("{}", e);
+        for overlay in &self.overlays {
+            if let Err(e) = unsafe { vroverlay.destroy_overlay_raw(overlay.handle) } {
+                eprintln!("{}", e);
+            }
         }
     }
 }
@@ -623,9 +841,13 @@ fn transition_layout(
 
 impl Vr for OpenVr {
     type Error = OpenVrError;
-    fn load_camera_paramter(&mut self) -> Option<StereoCamera> {
-        if let Some(cfg) = self.camera_config.as_ref() {
+    fn load_camera_paramter(&mut self, overlay: usize) -> Option<CameraCalibration> {
+        let vroverlay = &self.overlays[overlay];
+        if let Some(cfg) = vroverlay.camera_config.as_ref() {
             Some(*cfg)
+        } else if vroverlay.mono || !vroverlay.hmd_camera {
+            // The HMD's cameras have nothing to do with other cameras
+            None
         } else {
             // Load steam calibration data
             let hmd_id = self.sys.find_hmd()?;
@@ -651,48 +873,59 @@ impl Vr for OpenVr {
                 "{}",
                 serde_json::to_string(&lhcfg).unwrap_or("invalid json".to_owned())
             );
-            self.camera_config = Some(lhcfg);
-            Some(lhcfg)
+            self.overlays[overlay].camera_config = Some(CameraCalibration::Stereo(lhcfg));
+            self.overlays[overlay].camera_config
         }
     }
-    fn set_fallback_camera_config(&mut self, cfg: StereoCamera) {
+    fn set_fallback_camera_config(&mut self, overlay: usize, cfg: CameraCalibration) {
         log::warn!("Using fallback camera config");
-        self.camera_config = Some(cfg);
+        self.overlays[overlay].camera_config = Some(cfg);
     }
-    fn get_render_texture(&mut self) -> Result<Option<Arc<Image>>, Self::Error> {
+    fn get_render_texture(&mut self, overlay: usize) -> Result<Option<Arc<Image>>, Self::Error> {
         // log::debug!("get_render_texture");
-        if self.display_mode.projection_mode().is_none() {
-            assert!(self.render_texture.is_none());
-            self.render_texture =
-                Some(self.double_buffer[(self.texture_in_use ^ 1) as usize].clone());
+        let overlay = &mut self.overlays[overlay];
+        if overlay.display_mode.projection_mode().is_none() {
+            assert!(overlay.render_texture.is_none());
+            overlay.render_texture =
+                Some(overlay.double_buffer[(overlay.texture_in_use ^ 1) as usize].clone());
         }
-        assert!(self.render_texture.is_some());
-        Ok(self.render_texture.clone())
+        assert!(overlay.render_texture.is_some());
+        Ok(overlay.render_texture.clone())
     }
     fn submit_texture(
         &mut self,
-        elapsed: Duration,
+        overlay: usize,
+        capture_time: Duration,
         fov: &[[f32; 2]; 2],
     ) -> Result<(), Self::Error> {
+        let elapsed = crate::clock::monotonic_now().saturating_sub(capture_time);
         let hmd_transform = self.sys.hmd_transform(-elapsed.as_secs_f32()).cast::<f32>();
-        if self.reposition {
-            self.reposition = false;
-            self.position_mode.reposition(hmd_transform);
-
-            let transform: Matrix4<f32> = self.position_mode.transform(hmd_transform).into();
-            self.set_overlay_transformation(transform)?;
-        } else if matches!(self.position_mode, PositionMode::Hmd { .. }) {
-            let transform: Matrix4<f32> = self.position_mode.transform(hmd_transform).into();
-            self.set_overlay_transformation(transform)?;
+        let is_projected = self.overlays[overlay]
+            .display_mode
+            .projection_mode()
+            .is_some();
+        let (eye_to_head, ipd) = if is_projected {
+            (self.eye_to_head(), self.ipd()?)
+        } else {
+            Default::default()
+        };
+        let overlay = &mut self.overlays[overlay];
+        if overlay.reposition {
+            overlay.reposition = false;
+            overlay.position_mode.reposition(hmd_transform);
+
+            let transform: Matrix4<f32> = overlay.position_mode.transform(hmd_transform).into();
+            overlay.set_transformation(&self.sys, transform)?;
+        } else if matches!(overlay.position_mode, PositionMode::Hmd { .. }) {
+            let transform: Matrix4<f32> = overlay.position_mode.transform(hmd_transform).into();
+            overlay.set_transformation(&self.sys, transform)?;
         }
-        let output = if self.display_mode.projection_mode().is_some() {
-            let new_texture = self.double_buffer[(self.texture_in_use ^ 1) as usize].clone();
-            let eye_to_head = self.eye_to_head();
+        let output = if is_projected {
+            let new_texture = overlay.double_buffer[(overlay.texture_in_use ^ 1) as usize].clone();
             let view_transforms = eye_to_head.map(|m| hmd_transform * m);
-            let ipd = self.ipd()?;
-            let projector = self.projector.as_mut().unwrap();
+            let projector = overlay.projector.as_mut().unwrap();
             projector.update_mvps(
-                &self.overlay_transform,
+                &overlay.overlay_transform,
                 fov,
                 &view_transforms,
                 &hmd_transform,
@@ -709,7 +942,7 @@ impl Vr for OpenVr {
             future.then_signal_fence().wait(None)?;
             new_texture
         } else {
-            let output = self.render_texture.take().unwrap();
+            let output = overlay.render_texture.take().unwrap();
             transition_layout(
                 ImageLayout::ColorAttachmentOptimal,
                 &output,
@@ -725,14 +958,14 @@ impl Vr for OpenVr {
             _queue: self.queue.clone(),
             _instance: self.instance.clone(),
         };
-        self.texture.replace(texture);
+        overlay.texture.replace(texture);
         let vroverlay = self.sys.overlay();
         // Once we set a texture, the VRSystem starts to depend on Vulkan
         // instance being alive.
         self.sys.hold_vulkan_device(self.device.clone());
         let mut vrimage = openvr_sys2::VRVulkanTextureData_t {
-            m_nWidth: crate::CAMERA_SIZE * 2,
-            m_nHeight: crate::CAMERA_SIZE,
+            m_nWidth: overlay.extent[0],
+            m_nHeight: overlay.extent[1],
             m_nFormat: output.format() as u32,
             m_nSampleCount: output.samples() as u32,
             m_nImage: output.handle().as_raw(),
@@ -750,11 +983,11 @@ impl Vr for OpenVr {
         let ret = unsafe {
             vroverlay
                 .pin_mut()
-                .SetOverlayTexture(self.handle, &vrtexture)
+                .SetOverlayTexture(overlay.handle, &vrtexture)
                 .into_result()
                 .map_err(Into::into)
         };
-        self.texture_in_use ^= 1;
+        overlay.texture_in_use ^= 1;
         ret
     }
     fn is_synchronized(&self) -> bool {
@@ -765,41 +998,62 @@ impl Vr for OpenVr {
         std::thread::sleep(std::time::Duration::from_millis(100));
         Ok(())
     }
-    fn set_display_mode(&mut self, mode: DisplayMode) -> Result<(), Self::Error> {
-        if self.display_mode == mode {
+    fn set_display_mode(&mut self, overlay: usize, mode: DisplayMode) -> Result<(), Self::Error> {
+        if self.overlays[overlay].display_mode == mode {
             return Ok(());
         }
-        self.display_mode = mode;
-        if let Some(projection_mode) = self.display_mode.projection_mode() {
-            let camera_calib = self.load_camera_paramter();
-            if self.projector.is_none() {
-                self.render_texture = Some(crate::create_submittable_image(self.device.clone())?);
+        let camera_calib = mode
+            .projection_mode()
+            .and_then(|_| self.load_camera_paramter(overlay));
+        let overlay = &mut self.overlays[overlay];
+        overlay.display_mode = mode;
+        if let Some(projection_mode) = overlay.display_mode.projection_mode() {
+            if overlay.projector.is_none() {
+                // The camera image is projected for each eye, a mono camera only has one view
+                let [width, height] = overlay.extent;
+                let camera_extent = if overlay.mono {
+                    [width / 2, height]
+                } else {
+                    overlay.extent
+                };
+                overlay.render_texture = Some(crate::create_submittable_image(
+                    self.device.clone(),
+                    camera_extent,
+                )?);
                 let mut projector = crate::projection::Projection::new(
                     self.device.clone(),
                     self.allocator.clone(),
                     self.descriptor_set_allocator.clone(),
-                    self.render_texture.as_ref().unwrap(),
-                    1.0,
+                    overlay.render_texture.as_ref().unwrap(),
+                    overlay.size,
                     &camera_calib,
+                    overlay.mono,
                     ImageLayout::TransferSrcOptimal,
                 )?;
                 projector.set_mode(projection_mode);
-                self.projector = Some(projector);
+                overlay.projector = Some(projector);
             }
         } else {
-            self.render_texture = None;
-            self.projector = None;
+            overlay.render_texture = None;
+            overlay.projector = None;
         }
         self.sys
             .overlay()
             .pin_mut()
             .SetOverlayFlag(
-                self.handle,
+                overlay.handle,
                 openvr_sys2::VROverlayFlags::VROverlayFlags_SideBySide_Parallel,
-                self.display_mode.is_stereo(),
+                overlay.display_mode.texture_is_stereo(overlay.mono),
             )
             .into_result()?;
         let bounds = match mode {
+            // Without projection, the only view of a mono camera is in the left half
+            _ if overlay.mono && mode.projection_mode().is_none() => crate::vrapi::Bounds {
+                umin: 0.0,
+                umax: 0.5,
+                vmin: 0.0,
+                vmax: 1.0,
+            },
             DisplayMode::Flat { eye: Eye::Left } => crate::vrapi::Bounds {
                 umin: 0.0,
                 umax: 0.5,
@@ -819,38 +1073,96 @@ impl Vr for OpenVr {
                 vmax: 1.0,
             },
         };
-        self.set_overlay_texture_bounds_internal(bounds)
+        overlay.set_texture_bounds(&self.sys, bounds)
     }
-    fn show_overlay(&mut self) -> Result<(), Self::Error> {
+    fn set_z_order(&mut self, z_order: u32) -> Result<bool, Self::Error> {
+        for overlay in &self.overlays {
+            self.sys
+                .overlay()
+                .pin_mut()
+                .SetOverlaySortOrder(overlay.handle, z_order)
+                .into_result()?;
+        }
+        Ok(true)
+    }
+    fn set_size(&mut self, overlay: usize, size: f32) -> Result<(), Self::Error> {
+        let overlay = &mut self.overlays[overlay];
+        overlay.size = size;
+        if let Some(projector) = &mut overlay.projector {
+            projector.set_overlay_width(size);
+        }
         self.sys
             .overlay()
             .pin_mut()
-            .ShowOverlay(self.handle)
-            .into_result()
-            .map_err(Into::into)
+            .SetOverlayWidthInMeters(overlay.handle, size)
+            .into_result()?;
+        Ok(())
     }
-    fn hide_overlay(&mut self) -> Result<(), Self::Error> {
+    fn set_opacity(&mut self, overlay: usize, opacity: f32) -> Result<bool, Self::Error> {
         self.sys
             .overlay()
             .pin_mut()
-            .HideOverlay(self.handle)
-            .into_result()
-            .map_err(Into::into)
+            .SetOverlayAlpha(self.overlays[overlay].handle, opacity)
+            .into_result()?;
+        Ok(true)
     }
-    fn set_position_mode(&mut self, mode: PositionMode) -> Result<(), Self::Error> {
-        self.position_mode = mode;
+    fn show_overlay(&mut self) -> Result<(), Self::Error> {
+        for overlay in &self.overlays {
+            self.sys
+                .overlay()
+                .pin_mut()
+                .ShowOverlay(overlay.handle)
+                .into_result()?;
+        }
+        Ok(())
+    }
+    fn hide_overlay(&mut self) -> Result<(), Self::Error> {
+        for overlay in &self.overlays {
+            self.sys
+                .overlay()
+                .pin_mut()
+                .HideOverlay(overlay.handle)
+                .into_result()?;
+        }
+        Ok(())
+    }
+    fn set_position_mode(&mut self, overlay: usize, mode: PositionMode) -> Result<(), Self::Error> {
+        let overlay = &mut self.overlays[overlay];
+        overlay.position_mode = mode;
         match mode {
-            PositionMode::Absolute { transform } => {
+            PositionMode::Absolute { transform }
+            | PositionMode::Sticky {
+                transform: Some(transform),
+                ..
+            } => {
                 let transform: Matrix4<f32> = transform.into();
-                self.set_overlay_transformation(transform.cast())?;
+                overlay.set_transformation(&self.sys, transform.cast())?;
             }
-            PositionMode::Sticky { .. } => {
-                self.reposition = true;
+            PositionMode::Sticky {
+                transform: None, ..
+            } => {
+                overlay.reposition = true;
             }
             _ => (),
         }
         Ok(())
     }
+    fn position_mode(&self, overlay: usize) -> PositionMode {
+        self.overlays[overlay].position_mode
+    }
+    fn tracking_universe(&mut self) -> Result<Option<String>, Self::Error> {
+        let mut error = MaybeUninit::<_>::uninit();
+        let id = unsafe {
+            let id = self.sys.pin_mut().GetUint64TrackedDeviceProperty(
+                0,
+                openvr_sys2::ETrackedDeviceProperty::Prop_CurrentUniverseId_Uint64,
+                error.as_mut_ptr(),
+            );
+            error.assume_init().into_result()?;
+            id
+        };
+        Ok(Some(format!("openvr-{id}")))
+    }
     fn acknowledge_quit(&mut self) {
         self.sys.pin_mut().AcknowledgeQuit_Exiting();
     }
@@ -873,6 +1185,9 @@ impl Vr for OpenVr {
                     self.ipd = Some(ipd);
                 }
                 Ok(openvr_sys2::EVREventType::VREvent_Quit) => return Ok(Some(Event::RequestExit)),
+                Ok(openvr_sys2::EVREventType::VREvent_ChaperoneUniverseHasChanged) => {
+                    return Ok(Some(Event::TrackingUniverseChanged))
+                }
                 _ => (),
             }
         }
@@ -922,39 +1237,73 @@ impl Vr for OpenVr {
     }
 }
 
-pub(crate) struct OpenXr {
-    instance: openxr::Instance,
-    overlay_visible: bool,
+/// One composition layer, showing the image of one camera.
+struct OpenXrOverlay {
+    swapchain: openxr::Swapchain<openxr::Vulkan>,
+    swapchain_images: Vec<Arc<Image>>,
+    camera_config: Option<CameraCalibration>,
     position_mode: PositionMode,
     reposition: bool,
     display_mode: DisplayMode,
+    saved_overlay_pose: Option<openxr::Posef>,
+    projector: Option<crate::projection::Projection>,
+    render_texture: Option<Arc<Image>>,
+    /// size of the swapchain images, two views side by side
+    extent: [u32; 2],
+    /// the camera only has one view, which is put in the left half of the swapchain images
+    /// unless it's projected
+    mono: bool,
+    /// width of the layers, in meters
+    size: f32,
+    /// chained to the layers to make them see-through, if they are
+    color_scale_bias: Option<openxr::sys::CompositionLayerColorScaleBiasKHR>,
+}
+
+impl OpenXrOverlay {
+    /// Make `layer` see-through, if the overlay is.
+    fn blend<'a>(
+        &'a self,
+        layer: openxr::CompositionLayerQuad<'a, openxr::Vulkan>,
+    ) -> openxr::CompositionLayerQuad<'a, openxr::Vulkan> {
+        let Some(color_scale_bias) = &self.color_scale_bias else {
+            return layer;
+        };
+        let mut layer = layer
+            .layer_flags(openxr::CompositionLayerFlags::BLEND_TEXTURE_SOURCE_ALPHA)
+            .into_raw();
+        layer.next = color_scale_bias as *const _ as *const _;
+        // Safety: the layer borrows the overlay, so `color_scale_bias` outlives it
+        unsafe { openxr::CompositionLayerQuad::from_raw(layer) }
+    }
+}
+
+pub(crate) struct OpenXr {
+    instance: openxr::Instance,
+    overlay_visible: bool,
     allocator: Arc<StandardMemoryAllocator>,
     descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
     cmdbuf_allocator: Arc<StandardCommandBufferAllocator>,
     action_set: openxr::ActionSet,
-    action_button1: openxr::Action<bool>,
-    action_button2: openxr::Action<bool>,
-    action_debug: openxr::Action<bool>,
-    action_reposition: openxr::Action<bool>,
-    camera_config: Option<StereoCamera>,
+    /// one action for each `Action`, in order
+    actions: Vec<openxr::Action<bool>>,
 
     session_state: openxr::SessionState,
     session: openxr::Session<openxr::Vulkan>,
     frame_waiter: openxr::FrameWaiter,
     frame_stream: openxr::FrameStream<openxr::Vulkan>,
-    swapchain: openxr::Swapchain<openxr::Vulkan>,
-    swapchain_images: Vec<Arc<Image>>,
+    /// state of the frame that has begun, but not ended yet
     frame_state: Option<openxr::FrameState>,
+    /// where our layers are placed relative to other overlays, fixed for the session
+    placement: u32,
+    /// XR_KHR_composition_layer_color_scale_bias is enabled, which layer opacity needs
+    color_scale_bias: bool,
     space: openxr::Space,
     saved_poses: [(UnitQuaternion<f32>, Vector3<f32>); 2],
-    saved_overlay_pose: Option<openxr::Posef>,
+    overlays: Vec<OpenXrOverlay>,
 
     device: Arc<Device>,
     queue: Arc<Queue>,
     vk_instance: Arc<Instance>,
-
-    projector: Option<crate::projection::Projection>,
-    render_texture: Option<Arc<Image>>,
 }
 fn affine_to_posef(t: Affine3<f32>) -> openxr::Posef {
     let m = t.to_homogeneous();
@@ -1022,6 +1371,23 @@ pub(crate) enum OpenXrError {
 }
 
 impl OpenXr {
+    /// Convert a `CLOCK_MONOTONIC` time into the runtime's time.
+    fn convert_time(&self, time: Duration) -> Result<openxr::Time, OpenXrError> {
+        let convert = self
+            .instance
+            .exts()
+            .khr_convert_timespec_time
+            .as_ref()
+            .expect("XR_KHR_convert_timespec_time is enabled")
+            .convert_timespec_time_to_time;
+        let timespec = crate::clock::to_timespec(time);
+        let mut xr_time = openxr::Time::from_nanos(0);
+        let result = unsafe { convert(self.instance.as_raw(), &timespec, &mut xr_time) };
+        if result.into_raw() < 0 {
+            return Err(result.into());
+        }
+        Ok(xr_time)
+    }
     fn create_vk_device(
         xr_instance: &openxr::Instance,
         xr_system: openxr::SystemId,
@@ -1133,15 +1499,17 @@ impl OpenXr {
     }
 
     fn composition_layers<'a>(
-        saved_overlay_pose: &'a Option<openxr::Posef>,
-        swapchain: &'a openxr::Swapchain<openxr::Vulkan>,
+        overlay: &'a OpenXrOverlay,
         space: &'a openxr::Space,
-        is_stereo: bool,
-    ) -> Option<(
-        openxr::CompositionLayerQuad<'a, openxr::Vulkan>,
-        openxr::CompositionLayerQuad<'a, openxr::Vulkan>,
-    )> {
-        saved_overlay_pose.map(|overlay_posef| {
+    ) -> Option<[openxr::CompositionLayerQuad<'a, openxr::Vulkan>; 2]> {
+        // Each eye gets half of the camera image
+        let eye_extent = Extent2Di {
+            width: (overlay.extent[0] / 2) as i32,
+            height: overlay.extent[1] as i32,
+        };
+        let is_stereo = overlay.display_mode.texture_is_stereo(overlay.mono);
+        let swapchain = &overlay.swapchain;
+        overlay.saved_overlay_pose.map(|overlay_posef| {
             let left = openxr::CompositionLayerQuad::<openxr::Vulkan>::new()
                 .eye_visibility(EyeVisibility::LEFT)
                 .pose(overlay_posef)
@@ -1150,16 +1518,13 @@ impl OpenXr {
                         .swapchain(swapchain)
                         .image_rect(Rect2Di {
                             offset: Offset2Di { x: 0, y: 0 },
-                            extent: Extent2Di {
-                                width: crate::CAMERA_SIZE as i32,
-                                height: crate::CAMERA_SIZE as i32,
-                            },
+                            extent: eye_extent,
                         }),
                 )
                 .space(space)
                 .size(Extent2Df {
-                    width: 1.0,
-                    height: 1.0,
+                    width: overlay.size,
+                    height: overlay.size,
                 });
             let right = openxr::CompositionLayerQuad::<openxr::Vulkan>::new()
                 .eye_visibility(EyeVisibility::RIGHT)
@@ -1169,34 +1534,79 @@ impl OpenXr {
                         .swapchain(swapchain)
                         .image_rect(Rect2Di {
                             offset: Offset2Di {
-                                x: if is_stereo {
-                                    crate::CAMERA_SIZE as i32
-                                } else {
-                                    0
-                                },
+                                x: if is_stereo { eye_extent.width } else { 0 },
                                 y: 0,
                             },
-                            extent: Extent2Di {
-                                width: crate::CAMERA_SIZE as i32,
-                                height: crate::CAMERA_SIZE as i32,
-                            },
+                            extent: eye_extent,
                         }),
                 )
                 .space(space)
                 .size(Extent2Df {
-                    width: 1.0,
-                    height: 1.0,
+                    width: overlay.size,
+                    height: overlay.size,
                 });
-            (left, right)
+            [left, right].map(|layer| overlay.blend(layer))
         })
     }
 
-    pub(crate) fn new(placement: u32) -> Result<Self, OpenXrError> {
+    fn create_swapchain(
+        session: &openxr::Session<openxr::Vulkan>,
+        device: &Arc<Device>,
+        extent: [u32; 2],
+    ) -> Result<(openxr::Swapchain<openxr::Vulkan>, Vec<Arc<Image>>), OpenXrError> {
+        let swapchain = session.create_swapchain(&openxr::SwapchainCreateInfo {
+            array_size: 1,
+            face_count: 1,
+            create_flags: Default::default(),
+            usage_flags: openxr::SwapchainUsageFlags::COLOR_ATTACHMENT
+                | openxr::SwapchainUsageFlags::TRANSFER_DST,
+            format: vulkano::format::Format::R8G8B8A8_UNORM as u32,
+            sample_count: 1,
+            width: extent[0],
+            height: extent[1],
+            mip_count: 1,
+        })?;
+        log::debug!("created swapchain");
+        let swapchain_images = swapchain
+            .enumerate_images()?
+            .into_iter()
+            .map(|handle| {
+                let handle = ash::vk::Image::from_raw(handle);
+                let raw_image = unsafe {
+                    vulkano::image::sys::RawImage::from_handle_borrowed(
+                        device.clone(),
+                        handle,
+                        ImageCreateInfo {
+                            format: vulkano::format::Format::R8G8B8A8_UNORM,
+                            extent: [extent[0], extent[1], 1],
+                            usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_DST,
+                            ..Default::default()
+                        },
+                    )?
+                };
+                // SAFETY: OpenXR guarantees that the image is a swapchain image, thus has memory backing it.
+                let image = unsafe { raw_image.assume_bound() };
+                Ok::<_, OpenXrError>(Arc::new(image))
+            })
+            .try_collect()?;
+        log::debug!("got swapchain images");
+        Ok((swapchain, swapchain_images))
+    }
+
+    pub(crate) fn new(
+        placement: u32,
+        overlays: &[OverlayInfo],
+        bindings: &BindingsConfig,
+    ) -> Result<Self, OpenXrError> {
         let entry = unsafe { openxr::Entry::load()? };
         let mut extension = openxr::ExtensionSet::default();
         extension.extx_overlay = true;
         extension.khr_vulkan_enable2 = true;
         extension.khr_convert_timespec_time = true;
+        // Only needed for see-through overlays, so optional
+        extension.khr_composition_layer_color_scale_bias = entry
+            .enumerate_extensions()?
+            .khr_composition_layer_color_scale_bias;
         let instance = entry.create_instance(
             &ApplicationInfo {
                 application_name: crate::APP_NAME,
@@ -1264,109 +1674,50 @@ impl OpenXr {
         {
             return Err(OpenXrError::NoFormat);
         }
-        let swapchain = session.create_swapchain(&openxr::SwapchainCreateInfo {
-            array_size: 1,
-            face_count: 1,
-            create_flags: Default::default(),
-            usage_flags: openxr::SwapchainUsageFlags::COLOR_ATTACHMENT
-                | openxr::SwapchainUsageFlags::TRANSFER_DST,
-            format: vulkano::format::Format::R8G8B8A8_UNORM as u32,
-            sample_count: 1,
-            width: crate::CAMERA_SIZE * 2,
-            height: crate::CAMERA_SIZE,
-            mip_count: 1,
-        })?;
-        log::debug!("created swapchain");
-        let swapchain_images = swapchain
-            .enumerate_images()?
-            .into_iter()
-            .map(|handle| {
-                let handle = ash::vk::Image::from_raw(handle);
-                let raw_image = unsafe {
-                    vulkano::image::sys::RawImage::from_handle_borrowed(
-                        device.clone(),
-                        handle,
-                        ImageCreateInfo {
-                            format: vulkano::format::Format::R8G8B8A8_UNORM,
-                            extent: [CAMERA_SIZE * 2, CAMERA_SIZE, 1],
-                            usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_DST,
-                            ..Default::default()
-                        },
-                    )?
-                };
-                // SAFETY: OpenXR guarantees that the image is a swapchain image, thus has memory backing it.
-                let image = unsafe { raw_image.assume_bound() };
-                Ok::<_, OpenXrError>(Arc::new(image))
+        let overlays: Vec<_> = overlays
+            .iter()
+            .map(|info| {
+                let (swapchain, swapchain_images) =
+                    Self::create_swapchain(&session, &device, info.extent)?;
+                Ok::<_, OpenXrError>(OpenXrOverlay {
+                    swapchain,
+                    swapchain_images,
+                    camera_config: None,
+                    position_mode: PositionMode::default(),
+                    reposition: false,
+                    display_mode: DisplayMode::default(),
+                    saved_overlay_pose: None,
+                    projector: None,
+                    render_texture: None,
+                    extent: info.extent,
+                    mono: info.mono,
+                    size: 1.0,
+                    color_scale_bias: None,
+                })
             })
             .try_collect()?;
-        log::debug!("got swapchain images");
-        let action_button1 = action_set.create_action("button1", "Button1", &[])?;
-        let action_button2 = action_set.create_action("button2", "Button2", &[])?;
-        let action_debug = action_set.create_action("debug", "Debug", &[])?;
-        let action_reposition = action_set.create_action("reposition", "Reposition", &[])?;
-        instance.suggest_interaction_profile_bindings(
-            instance.string_to_path("/interaction_profiles/htc/vive_controller")?,
-            &[
-                openxr::Binding::new(
-                    &action_button1,
-                    instance.string_to_path("/user/hand/left/input/menu/click")?,
-                ),
-                openxr::Binding::new(
-                    &action_button2,
-                    instance.string_to_path("/user/hand/right/input/menu/click")?,
-                ),
-                openxr::Binding::new(
-                    &action_debug,
-                    instance.string_to_path("/user/hand/left/input/trigger/click")?,
-                ),
-                openxr::Binding::new(
-                    &action_reposition,
-                    instance.string_to_path("/user/hand/right/input/trigger/click")?,
-                ),
-            ],
-        )?;
-        instance.suggest_interaction_profile_bindings(
-            instance.string_to_path("/interaction_profiles/valve/index_controller")?,
-            &[
-                openxr::Binding::new(
-                    &action_button1,
-                    instance.string_to_path("/user/hand/left/input/b/click")?,
-                ),
-                openxr::Binding::new(
-                    &action_button2,
-                    instance.string_to_path("/user/hand/right/input/b/click")?,
-                ),
-                openxr::Binding::new(
-                    &action_debug,
-                    instance.string_to_path("/user/hand/left/input/trigger/click")?,
-                ),
-                openxr::Binding::new(
-                    &action_reposition,
-                    instance.string_to_path("/user/hand/right/input/a/click")?,
-                ),
-            ],
-        )?;
-        instance.suggest_interaction_profile_bindings(
-            instance.string_to_path("/interaction_profiles/khr/simple_controller")?,
-            &[
-                openxr::Binding::new(
-                    &action_button1,
-                    instance.string_to_path("/user/hand/left/input/menu/click")?,
-                ),
-                openxr::Binding::new(
-                    &action_button2,
-                    instance.string_to_path("/user/hand/right/input/menu/click")?,
-                ),
-                openxr::Binding::new(
-                    &action_debug,
-                    instance.string_to_path("/user/hand/left/input/select/click")?,
-                ),
-                openxr::Binding::new(
-                    &action_reposition,
-                    instance.string_to_path("/user/hand/right/input/select/click")?,
-                ),
-            ],
-        )?;
+        let actions: Vec<_> = Action::ALL
+            .into_iter()
+            .map(|action| action_set.create_action(action.name(), action.localized_name(), &[]))
+            .try_collect()?;
+        for (profile, suggested) in crate::bindings::openxr_bindings(bindings) {
+            if suggested.is_empty() {
+                continue;
+            }
+            let suggested: Vec<_> = suggested
+                .iter()
+                .map(|(action, path)| {
+                    Ok::<_, OpenXrError>(openxr::Binding::new(
+                        &actions[*action as usize],
+                        instance.string_to_path(path)?,
+                    ))
+                })
+                .try_collect()?;
+            instance.suggest_interaction_profile_bindings(
+                instance.string_to_path(profile)?,
+                &suggested,
+            )?;
+        }
         let space =
             session.create_reference_space(ReferenceSpaceType::STAGE, openxr::Posef::IDENTITY)?;
         log::debug!("created actions");
@@ -1374,15 +1725,8 @@ impl OpenXr {
 
         Ok(Self {
             instance,
-            camera_config: None,
             overlay_visible: false,
-            position_mode: PositionMode::default(),
-            reposition: false,
-            display_mode: DisplayMode::default(),
-            action_button1,
-            action_button2,
-            action_debug,
-            action_reposition,
+            actions,
 
             action_set,
 
@@ -1390,12 +1734,12 @@ impl OpenXr {
             session,
             frame_waiter,
             frame_stream,
-            swapchain,
-            swapchain_images,
             frame_state: None,
+            placement,
+            color_scale_bias: extension.khr_composition_layer_color_scale_bias,
             space,
             saved_poses: [Default::default(); 2],
-            saved_overlay_pose: None,
+            overlays,
 
             allocator,
             descriptor_set_allocator,
@@ -1403,9 +1747,6 @@ impl OpenXr {
             vk_instance,
             device,
             queue,
-
-            projector: None,
-            render_texture: None,
         })
     }
 }
@@ -1444,24 +1785,22 @@ impl Vr for OpenXr {
 
     type Error = OpenXrError;
 
-    fn load_camera_paramter(&mut self) -> Option<StereoCamera> {
-        self.camera_config
+    fn load_camera_paramter(&mut self, overlay: usize) -> Option<CameraCalibration> {
+        self.overlays[overlay].camera_config
     }
 
-    fn set_fallback_camera_config(&mut self, cfg: StereoCamera) {
-        self.camera_config = Some(cfg);
+    fn set_fallback_camera_config(&mut self, overlay: usize, cfg: CameraCalibration) {
+        self.overlays[overlay].camera_config = Some(cfg);
     }
 
     fn submit_texture(
         &mut self,
-        elapsed: Duration,
+        overlay: usize,
+        capture_time: Duration,
         fov: &[[f32; 2]; 2],
     ) -> Result<(), Self::Error> {
         log::trace!("submit texture");
-        let frame_state = self.frame_state.as_ref().unwrap();
-        let now = self.instance.now()?;
-        let time_at_capture =
-            openxr::Time::from_nanos((now.as_nanos() as u128 - elapsed.as_nanos()) as i64);
+        let time_at_capture = self.convert_time(capture_time)?;
         let (view_state_flags, views) = self.session.locate_views(
             ViewConfigurationType::PRIMARY_STEREO,
             time_at_capture,
@@ -1483,16 +1822,17 @@ impl Vr for OpenXr {
         );
         let center: Translation3<f32> = ((view_poses[0].1 + view_poses[1].1) / 2.0).into();
         let hmd_transform = center.to_homogeneous() * rotation_center.to_homogeneous();
-        if self.reposition {
-            self.position_mode.reposition(hmd_transform);
-            self.reposition = false;
+        let overlay = &mut self.overlays[overlay];
+        if overlay.reposition {
+            overlay.position_mode.reposition(hmd_transform);
+            overlay.reposition = false;
         }
-        let transform = self.position_mode.transform(hmd_transform);
+        let transform = overlay.position_mode.transform(hmd_transform);
         let overlay_posef = affine_to_posef(transform);
-        self.saved_overlay_pose = Some(overlay_posef);
-        if self.display_mode.projection_mode().is_some() {
+        overlay.saved_overlay_pose = Some(overlay_posef);
+        if overlay.display_mode.projection_mode().is_some() {
             // Apply projection
-            let image = self.swapchain.acquire_image()? as usize;
+            let image = overlay.swapchain.acquire_image()? as usize;
             let view_transforms = [
                 Translation3::from(view_poses[0].1).to_homogeneous()
                     * view_poses[0].0.to_homogeneous(),
@@ -1500,9 +1840,9 @@ impl Vr for OpenXr {
                     * view_poses[1].0.to_homogeneous(),
             ];
             let ipd = view_poses[1].1.x - view_poses[0].1.x;
-            self.swapchain.wait_image(openxr::Duration::INFINITE)?;
-            let output = self.swapchain_images[image].clone();
-            let projector = self.projector.as_mut().unwrap();
+            overlay.swapchain.wait_image(openxr::Duration::INFINITE)?;
+            let output = overlay.swapchain_images[image].clone();
+            let projector = overlay.projector.as_mut().unwrap();
             projector.update_mvps(transform.matrix(), fov, &view_transforms, &hmd_transform)?;
             projector.set_ipd(ipd);
             let future = projector.project(
@@ -1515,20 +1855,34 @@ impl Vr for OpenXr {
             future.flush()?;
             future.then_signal_fence().wait(None)?;
         } else {
-            self.render_texture.take();
+            overlay.render_texture.take();
         }
-        self.swapchain.release_image()?;
-        let (left, right) = Self::composition_layers(
-            &self.saved_overlay_pose,
-            &self.swapchain,
-            &self.space,
-            self.display_mode.is_stereo(),
-        )
-        .unwrap();
+        overlay.swapchain.release_image()?;
+        Ok(())
+    }
+
+    fn end_frame(&mut self) -> Result<(), Self::Error> {
+        let Some(frame_state) = self.frame_state.take() else {
+            return Ok(());
+        };
+        let layers = if frame_state.should_render {
+            self.overlays
+                .iter()
+                .filter_map(|overlay| Self::composition_layers(overlay, &self.space))
+                .flatten()
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+        log::trace!(
+            "end frame with {} layers {:?}",
+            layers.len(),
+            frame_state.predicted_display_time
+        );
         self.frame_stream.end(
             frame_state.predicted_display_time,
             EnvironmentBlendMode::OPAQUE,
-            &[&left, &right],
+            &layers.iter().map(|layer| &**layer).collect::<Vec<_>>(),
         )?;
         Ok(())
     }
@@ -1543,32 +1897,13 @@ impl Vr for OpenXr {
             std::thread::sleep(std::time::Duration::from_millis(100));
             return Ok(());
         }
-        let frame_state = self.frame_state.insert(self.frame_waiter.wait()?);
+        // Reuse the last images of all the overlays
+        self.frame_state = Some(self.frame_waiter.wait()?);
         self.frame_stream.begin()?;
-        if let Some((left, right)) = Self::composition_layers(
-            &self.saved_overlay_pose,
-            &self.swapchain,
-            &self.space,
-            self.display_mode.is_stereo(),
-        ) {
-            log::trace!("reuse last image {:?}", frame_state.predicted_display_time);
-            self.frame_stream.end(
-                frame_state.predicted_display_time,
-                EnvironmentBlendMode::OPAQUE,
-                &[&left, &right],
-            )?;
-        } else {
-            log::trace!("no saved overlay pose");
-            self.frame_stream.end(
-                frame_state.predicted_display_time,
-                EnvironmentBlendMode::OPAQUE,
-                &[],
-            )?;
-        }
-        Ok(())
+        self.end_frame()
     }
 
-    fn get_render_texture(&mut self) -> Result<Option<Arc<Image>>, Self::Error> {
+    fn get_render_texture(&mut self, overlay: usize) -> Result<Option<Arc<Image>>, Self::Error> {
         if (self.session_state != openxr::SessionState::FOCUSED
             && self.session_state != openxr::SessionState::VISIBLE
             && self.session_state != openxr::SessionState::SYNCHRONIZED
@@ -1578,53 +1913,101 @@ impl Vr for OpenXr {
             log::debug!("VR runtime not ready");
             return Ok(None);
         }
-        let frame_state = self.frame_state.insert(self.frame_waiter.wait()?);
-        self.frame_stream.begin()?;
+        // All the overlays share one frame, it's begun by whichever needs it first
+        let frame_state = match self.frame_state {
+            Some(frame_state) => frame_state,
+            None => {
+                let frame_state = self.frame_waiter.wait()?;
+                self.frame_stream.begin()?;
+                self.frame_state = Some(frame_state);
+                frame_state
+            }
+        };
         if !frame_state.should_render {
-            self.frame_stream.end(
-                frame_state.predicted_display_time,
-                EnvironmentBlendMode::OPAQUE,
-                &[],
-            )?;
             return Ok(None);
         }
-        if self.display_mode.projection_mode().is_some() {
+        let overlay = &mut self.overlays[overlay];
+        if overlay.display_mode.projection_mode().is_some() {
             log::trace!("render to intermediate texture");
-            assert!(self.render_texture.is_some());
-            return Ok(self.render_texture.clone());
+            assert!(overlay.render_texture.is_some());
+            return Ok(overlay.render_texture.clone());
         }
         log::trace!("render to swapchain image");
-        let image = self.swapchain.acquire_image()? as usize;
-        self.render_texture = Some(self.swapchain_images[image].clone());
-        self.swapchain.wait_image(openxr::Duration::INFINITE)?;
-        Ok(self.render_texture.clone())
-    }
-
-    fn set_display_mode(&mut self, mode: DisplayMode) -> Result<(), Self::Error> {
-        self.display_mode = mode;
-        if let Some(projection_mode) = self.display_mode.projection_mode() {
-            let camera_calib = self.load_camera_paramter();
-            if self.projector.is_none() {
-                self.render_texture = Some(crate::create_submittable_image(self.device.clone())?);
+        let image = overlay.swapchain.acquire_image()? as usize;
+        overlay.render_texture = Some(overlay.swapchain_images[image].clone());
+        overlay.swapchain.wait_image(openxr::Duration::INFINITE)?;
+        Ok(overlay.render_texture.clone())
+    }
+
+    fn set_display_mode(&mut self, overlay: usize, mode: DisplayMode) -> Result<(), Self::Error> {
+        let overlay = &mut self.overlays[overlay];
+        overlay.display_mode = mode;
+        if let Some(projection_mode) = overlay.display_mode.projection_mode() {
+            let camera_calib = overlay.camera_config;
+            if overlay.projector.is_none() {
+                // The camera image is projected for each eye, a mono camera only has one view
+                let [width, height] = overlay.extent;
+                let camera_extent = if overlay.mono {
+                    [width / 2, height]
+                } else {
+                    overlay.extent
+                };
+                overlay.render_texture = Some(crate::create_submittable_image(
+                    self.device.clone(),
+                    camera_extent,
+                )?);
                 let mut projector = crate::projection::Projection::new(
                     self.device.clone(),
                     self.allocator.clone(),
                     self.descriptor_set_allocator.clone(),
-                    self.render_texture.as_ref().unwrap(),
-                    1.0,
+                    overlay.render_texture.as_ref().unwrap(),
+                    overlay.size,
                     &camera_calib,
+                    overlay.mono,
                     ImageLayout::ColorAttachmentOptimal,
                 )?;
                 projector.set_mode(projection_mode);
-                self.projector = Some(projector);
+                overlay.projector = Some(projector);
             }
         } else {
-            self.render_texture = None;
-            self.projector = None;
+            overlay.render_texture = None;
+            overlay.projector = None;
         }
         Ok(())
     }
 
+    fn set_z_order(&mut self, z_order: u32) -> Result<bool, Self::Error> {
+        // Placement is part of the session, and can't be changed afterwards
+        Ok(z_order == self.placement)
+    }
+    fn set_size(&mut self, overlay: usize, size: f32) -> Result<(), Self::Error> {
+        let overlay = &mut self.overlays[overlay];
+        overlay.size = size;
+        if let Some(projector) = &mut overlay.projector {
+            projector.set_overlay_width(size);
+        }
+        Ok(())
+    }
+    fn set_opacity(&mut self, overlay: usize, opacity: f32) -> Result<bool, Self::Error> {
+        if !self.color_scale_bias {
+            return Ok(opacity >= 1.0);
+        }
+        // The alpha is premultiplied, so the colors are scaled as well
+        let color = |value| openxr::sys::Color4f {
+            r: value,
+            g: value,
+            b: value,
+            a: value,
+        };
+        self.overlays[overlay].color_scale_bias =
+            (opacity < 1.0).then_some(openxr::sys::CompositionLayerColorScaleBiasKHR {
+                ty: openxr::sys::CompositionLayerColorScaleBiasKHR::TYPE,
+                next: std::ptr::null(),
+                color_scale: color(opacity),
+                color_bias: color(0.0),
+            });
+        Ok(true)
+    }
     fn show_overlay(&mut self) -> Result<(), Self::Error> {
         if !self.overlay_visible {
             log::debug!("show overlay, {:?}", self.session_state);
@@ -1644,7 +2027,7 @@ impl Vr for OpenXr {
                 .pose(openxr::Posef::IDENTITY)
                 .sub_image(
                     SwapchainSubImage::new()
-                        .swapchain(&self.swapchain)
+                        .swapchain(&self.overlays[0].swapchain)
                         .image_rect(Rect2Di {
                             offset: Offset2Di { x: 0, y: 0 },
                             extent: Extent2Di {
@@ -1668,13 +2051,41 @@ impl Vr for OpenXr {
         Ok(())
     }
 
-    fn set_position_mode(&mut self, mode: PositionMode) -> Result<(), Self::Error> {
-        self.position_mode = mode;
-        if matches!(mode, PositionMode::Sticky { .. }) {
-            self.reposition = true;
+    fn set_position_mode(&mut self, overlay: usize, mode: PositionMode) -> Result<(), Self::Error> {
+        let overlay = &mut self.overlays[overlay];
+        overlay.position_mode = mode;
+        if matches!(
+            mode,
+            PositionMode::Sticky {
+                transform: None,
+                ..
+            }
+        ) {
+            overlay.reposition = true;
         }
         Ok(())
     }
+    fn position_mode(&self, overlay: usize) -> PositionMode {
+        self.overlays[overlay].position_mode
+    }
+    fn tracking_universe(&mut self) -> Result<Option<String>, Self::Error> {
+        // OpenXR has no id for the room setup the stage space is from, so tell them apart
+        // by the size of the play area. Where the stage is relative to other spaces isn't
+        // stable, the LOCAL space moves with the head at the start of each session.
+        let Some(bounds) = self
+            .session
+            .reference_space_bounds_rect(ReferenceSpaceType::STAGE)?
+        else {
+            log::info!("play area bounds unknown, overlay placements won't be remembered");
+            return Ok(None);
+        };
+        let cm = |x: f32| (x * 100.0).round() as i32;
+        Ok(Some(format!(
+            "openxr-{}x{}",
+            cm(bounds.width),
+            cm(bounds.height)
+        )))
+    }
 
     fn poll_next_event(&mut self) -> Result<Option<Event>, Self::Error> {
         let mut event = EventDataBuffer::default();
@@ -1710,6 +2121,11 @@ impl Vr for OpenXr {
                         _ => (),
                     }
                 }
+                XrEvent::ReferenceSpaceChangePending(change)
+                    if change.reference_space_type() == ReferenceSpaceType::STAGE =>
+                {
+                    break Some(Event::TrackingUniverseChanged);
+                }
                 XrEvent::EventsLost(_) => (), // ? should we do something?
                 _ => (),
             }
@@ -1724,19 +2140,9 @@ impl Vr for OpenXr {
     }
 
     fn get_action_state(&self, action: Action) -> Result<bool, Self::Error> {
-        Ok(match action {
-            Action::Button1 => self
-                .action_button1
-                .state(&self.session, openxr::Path::NULL)?,
-            Action::Button2 => self
-                .action_button2
-                .state(&self.session, openxr::Path::NULL)?,
-            Action::Debug => self.action_debug.state(&self.session, openxr::Path::NULL)?,
-            Action::Reposition => self
-                .action_reposition
-                .state(&self.session, openxr::Path::NULL)?,
-        }
-        .current_state)
+        Ok(self.actions[action as usize]
+            .state(&self.session, openxr::Path::NULL)?
+            .current_state)
     }
 
     fn wait_for_ready(&mut self) -> Result<(), Self::Error> {
@@ -1754,7 +2160,7 @@ impl Vr for OpenXr {
             && self.session_state != openxr::SessionState::FOCUSED
             && self.session_state != openxr::SessionState::VISIBLE
         {
-            let frame_state = self.frame_state.insert(self.frame_waiter.wait()?);
+            let frame_state = self.frame_waiter.wait()?;
             self.frame_stream.begin()?;
             self.frame_stream.end(
                 frame_state.predicted_display_time,
@@ -0,0 +1,77 @@
+//! Watch the config file for changes, so they can be applied while running.
+use std::{
+    ffi::{CString, OsString},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+pub(crate) struct ConfigWatcher {
+    inotify: OwnedFd,
+    file_name: OsString,
+}
+
+impl ConfigWatcher {
+    pub(crate) fn new(path: &Path) -> Result<Self> {
+        // Watch the directory rather than the file, editors often save by replacing the
+        // file, which would end a watch on the file itself.
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let file_name = path
+            .file_name()
+            .with_context(|| anyhow!("{} is not a file", path.display()))?
+            .to_owned();
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let inotify = unsafe { OwnedFd::from_raw_fd(fd) };
+        let dir_name = CString::new(dir.as_os_str().as_bytes())?;
+        // Only look at files that are completely written
+        let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+        if unsafe { libc::inotify_add_watch(inotify.as_raw_fd(), dir_name.as_ptr(), mask) } < 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| anyhow!("cannot watch {}", dir.display()));
+        }
+        log::debug!("watching {} for changes", path.display());
+        Ok(Self { inotify, file_name })
+    }
+    /// Whether the config file has been written since the last time this is called.
+    /// Never blocks.
+    pub(crate) fn changed(&mut self) -> bool {
+        const HEADER_SIZE: usize = std::mem::size_of::<libc::inotify_event>();
+        let mut buffer = [0u8; 4096];
+        let mut changed = false;
+        loop {
+            let len = unsafe {
+                libc::read(
+                    self.inotify.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                )
+            };
+            if len <= 0 {
+                // Nothing more to read, or the error will show up again next time
+                break;
+            }
+            let mut events = &buffer[..len as usize];
+            while events.len() >= HEADER_SIZE {
+                // The buffer is not aligned for `inotify_event`, so read the fields we need
+                // by hand. `len` is the size of the name following the header.
+                let name_len = u32::from_ne_bytes(events[12..16].try_into().unwrap()) as usize;
+                let name = &events[HEADER_SIZE..(HEADER_SIZE + name_len).min(events.len())];
+                // The name is padded with NULs
+                let name = name.split(|b| *b == 0).next().unwrap_or_default();
+                changed |= name == self.file_name.as_bytes();
+                events = &events[(HEADER_SIZE + name_len).min(events.len())..];
+            }
+        }
+        changed
+    }
+}
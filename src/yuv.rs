@@ -90,6 +90,8 @@ impl std::fmt::Debug for GpuYuyvConverter {
 impl GpuYuyvConverter {
     /// Create a new YUYV to RGBA8 converter.
     /// Note the input image's width has to be `w/2`, and `w` has to be even.
+    /// The output image can be larger than `w`x`h`, e.g. when a single view is written
+    /// into one half of a stereo texture, only its top left corner is written.
     pub fn new(
         device: Arc<Device>,
         descriptor_set_allocator: Arc<dyn DescriptorSetAllocator>,